@@ -1,25 +1,269 @@
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
+use regex::{Captures, Regex};
 use zellij_tile::prelude::*;
 
+use crate::ansi;
 use crate::config::Config;
+use crate::pane_capture::CONTEXT_KIND;
 
-/// Execute the configured action for the matched text.
-pub fn execute_action(config: &Config, text: &str) {
-    let action = &config.action;
+/// `RunCommandResult` context of commands run by actions.
+pub const KIND_ACTION: &str = "action";
+const CONTEXT_ACTION: &str = "action";
+const CONTEXT_CHARS: &str = "chars";
+const CONTEXT_STEP: &str = "step";
+const CONTEXT_TEXT: &str = "text";
 
+/// Actions that run a command in a new pane, followed by the command.
+const PANE_ACTIONS: [&str; 4] = [":pane:", ":pane-floating:", ":pane-tiled:", ":pane-tab:"];
+
+/// Builtin patterns whose matches are file paths.
+const PATH_PATTERNS: &[&str] = &["path", "file-line", "git-status", "diff"];
+
+/// What the plugin has to do after starting an action.
+#[derive(Debug, PartialEq)]
+pub enum ActionOutcome {
+    /// Nothing to wait for.
+    Finished,
+    /// A command was started; its result arrives as a `RunCommandResult`.
+    Running,
+    /// An escape sequence for the terminal emulator (OSC 52) that the plugin
+    /// has to print before closing.
+    Output(String),
+}
+
+/// One selected match and the pane it was captured from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub text: String,
+    pub pane_id: Option<u32>,
+}
+
+/// The selected text and where it was found.
+#[derive(Clone)]
+pub struct Selection {
+    pub text: String,
+    /// Name of the pattern that matched; `None` for a multi-select.
+    pub pattern: Option<String>,
+    /// The terminal pane the match was captured from. For a multi-select,
+    /// only set when every match comes from the same pane.
+    pub pane_id: Option<u32>,
+    /// Line within the pane's capture and display column, both 0-based.
+    pub position: Option<(usize, usize)>,
+    /// Lines to scroll the pane up from the bottom of its scrollback so the
+    /// row holding the match is at the top of the pane.
+    pub jump_offset: Option<usize>,
+    /// Content rows of that pane, which is how far a page scroll moves it.
+    pub pane_rows: Option<usize>,
+    /// Working directory of that pane, if known.
+    pub cwd: Option<PathBuf>,
+    /// Named groups of the matching pattern, e.g. `file` and `lnum`.
+    pub groups: BTreeMap<String, String>,
+    /// Every selected match: one for a single selection, all of them in
+    /// multi-select mode.
+    pub items: Vec<Item>,
+}
+
+/// Execute `action` for the selection. `target_pane_id` is the pane the
+/// plugin was launched for, which is where `:paste:` types.
+///
+/// The `RunCommandResult` of a command started by an action is tagged with
+/// [`KIND_ACTION`] and `step`, the action's index in its pipeline (see
+/// [`result_step`]), and can be passed to [`describe_result`].
+pub fn execute_action(
+    config: &Config,
+    action: &str,
+    step: usize,
+    selection: &Selection,
+    target_pane_id: Option<u32>,
+) -> ActionOutcome {
     if action.is_empty() {
-        return;
+        return ActionOutcome::Finished;
     }
 
-    match action.as_str() {
-        ":copy:" => copy_to_clipboard(config, text),
-        ":open:" => open_url(config, text),
-        _ => run_custom_action(action, text),
+    let absolute;
+    let selection = match absolutize(config, selection) {
+        Some(resolved) => {
+            absolute = resolved;
+            &absolute
+        }
+        None => selection,
+    };
+    if let Some((placement, command)) = pane_action(action) {
+        run_in_pane(placement, command, selection);
+        return ActionOutcome::Finished;
+    }
+
+    let text = selection.text.as_str();
+    let context = action_context(action, text, step);
+    match action {
+        ":copy:" => return copy_to_clipboard(config, selection, context),
+        ":open:" => open_url(config, selection, context),
+        ":paste:" => {
+            paste(config, text, target_pane_id);
+            return ActionOutcome::Finished;
+        }
+        ":jump:" => {
+            jump(selection);
+            return ActionOutcome::Finished;
+        }
+        ":edit:" => {
+            edit(config, selection);
+            return ActionOutcome::Finished;
+        }
+        _ => run_custom_action(action, selection, context),
+    }
+    ActionOutcome::Running
+}
+
+/// Join multi-selected matches as configured by `multi_format` and
+/// `multi_separator`. A JSON array ignores the separator.
+pub fn format_multi(config: &Config, items: &[String]) -> String {
+    match config.multi_format.as_str() {
+        "json" => {
+            let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+            format!("[{}]", items.join(","))
+        }
+        "shell-quoted" => {
+            let items: Vec<String> = items.iter().map(|item| shell_escape(item)).collect();
+            items.join(&config.multi_separator)
+        }
+        _ => items.join(&config.multi_separator),
     }
 }
 
-fn copy_to_clipboard(config: &Config, text: &str) {
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Interpret the result of an action's command: a confirmation to show, if
+/// any, or an error message including the command's stderr.
+pub fn describe_result(
+    exit_code: Option<i32>,
+    stderr: &[u8],
+    context: &BTreeMap<String, String>,
+) -> Result<Option<String>, String> {
+    let action = context.get(CONTEXT_ACTION).map_or("", String::as_str);
+    if exit_code != Some(0) {
+        let exit =
+            exit_code.map_or_else(|| "killed".to_string(), |code| format!("exit code {code}"));
+        let stderr = String::from_utf8_lossy(stderr);
+        let stderr = stderr.trim();
+        return Err(if stderr.is_empty() {
+            format!("Action `{action}` failed ({exit})")
+        } else {
+            format!("Action `{action}` failed ({exit})\n{stderr}")
+        });
+    }
+
+    Ok(match (action, context.get(CONTEXT_CHARS)) {
+        (":copy:", Some(chars)) => Some(format!("Copied {chars} chars")),
+        _ => None,
+    })
+}
+
+/// The OSC 52 sequence and confirmation to fall back to when copying with
+/// the local clipboard tools failed, if the clipboard backend allows it.
+pub fn osc52_fallback(context: &BTreeMap<String, String>) -> Option<(String, String)> {
+    context
+        .get(CONTEXT_TEXT)
+        .map(|text| (ansi::osc52(text), copied_notice(text)))
+}
+
+/// Confirmation for a copy done without a command.
+pub fn copied_notice(text: &str) -> String {
+    format!("Copied {} chars", text.chars().count())
+}
+
+/// The pipeline step an action's result belongs to.
+pub fn result_step(context: &BTreeMap<String, String>) -> Option<usize> {
+    context.get(CONTEXT_STEP)?.parse().ok()
+}
+
+fn action_context(action: &str, text: &str, step: usize) -> BTreeMap<String, String> {
+    BTreeMap::from([
+        (CONTEXT_KIND.to_string(), KIND_ACTION.to_string()),
+        (CONTEXT_ACTION.to_string(), action.to_string()),
+        (CONTEXT_CHARS.to_string(), text.chars().count().to_string()),
+        (CONTEXT_STEP.to_string(), step.to_string()),
+    ])
+}
+
+/// The selection with a relative path match made absolute, if
+/// `absolute_paths` is set and the pane's working directory is known.
+fn absolutize(config: &Config, selection: &Selection) -> Option<Selection> {
+    if !config.absolute_paths {
+        return None;
+    }
+    let pattern = selection.pattern.as_deref()?;
+    let cwd = selection.cwd.as_ref()?;
+    let text = absolute_path(&selection.text, pattern, cwd)?;
+    Some(Selection {
+        items: vec![Item {
+            text: text.clone(),
+            pane_id: selection.pane_id,
+        }],
+        text,
+        ..selection.clone()
+    })
+}
+
+/// A multi-selected match, made absolute against the working directory of
+/// its own pane like [`absolutize`] does for a single selection.
+pub fn absolutize_item(config: &Config, text: &str, pattern: &str, cwd: Option<&Path>) -> String {
+    cwd.filter(|_| config.absolute_paths)
+        .and_then(|cwd| absolute_path(text, pattern, cwd))
+        .unwrap_or_else(|| text.to_string())
+}
+
+fn absolute_path(text: &str, pattern: &str, cwd: &Path) -> Option<String> {
+    if !PATH_PATTERNS.contains(&pattern) || text.starts_with(['/', '~', '$']) {
+        return None;
+    }
+    let relative = text.strip_prefix("./").unwrap_or(text);
+    Some(cwd.join(relative).display().to_string())
+}
+
+/// Run `cmd` in the working directory of the pane the match came from, so
+/// relative paths mean what they meant there.
+fn spawn(cmd: &[&str], selection: &Selection, context: BTreeMap<String, String>) {
+    match selection.cwd {
+        Some(ref cwd) => {
+            run_command_with_env_variables_and_cwd(cmd, BTreeMap::new(), cwd.clone(), context)
+        }
+        None => run_command(cmd, context),
+    }
+}
+
+fn copy_to_clipboard(
+    config: &Config,
+    selection: &Selection,
+    mut context: BTreeMap<String, String>,
+) -> ActionOutcome {
+    let text = selection.text.as_str();
+    match config.clipboard_backend.as_str() {
+        "osc52" => return ActionOutcome::Output(ansi::osc52(text)),
+        // Keep the text around in case no local tool works
+        "auto" if config.clipboard_command.is_none() => {
+            context.insert(CONTEXT_TEXT.to_string(), text.to_string());
+        }
+        _ => {}
+    }
+
     let cmd = if let Some(ref clipboard_cmd) = config.clipboard_command {
         clipboard_cmd.clone()
     } else {
@@ -28,45 +272,580 @@ fn copy_to_clipboard(config: &Config, text: &str) {
             "{ pbcopy 2>/dev/null || wl-copy 2>/dev/null || ",
             "xclip -selection clipboard 2>/dev/null || ",
             "xsel -i --clipboard 2>/dev/null || ",
-            "clip.exe 2>/dev/null; }"
+            "clip.exe 2>/dev/null || ",
+            "{ echo 'no clipboard tool succeeded (tried pbcopy, wl-copy, xclip, xsel, clip.exe)' >&2; exit 1; }; }"
         )
         .to_string()
     };
 
-    let escaped = shell_escape(text);
-    let full_cmd = format!("printf '%s' {} | {}", escaped, cmd);
+    let full_cmd = format!("{} | {}", printf_text(text), cmd);
 
-    let context = BTreeMap::new();
-    run_command(&["sh", "-c", &full_cmd], context);
+    spawn(&["sh", "-c", &full_cmd], selection, context);
+    ActionOutcome::Running
 }
 
-fn open_url(config: &Config, text: &str) {
-    let context = BTreeMap::new();
-
+fn open_url(config: &Config, selection: &Selection, context: BTreeMap<String, String>) {
+    let text = selection.text.as_str();
     if let Some(ref open_cmd) = config.open_command {
         let escaped = shell_escape(text);
         let full_cmd = format!("{} {}", open_cmd, escaped);
-        run_command(&["sh", "-c", &full_cmd], context);
+        spawn(&["sh", "-c", &full_cmd], selection, context);
+    } else {
+        // Use the first opener that exists, so there is a single result to report
+        let script = concat!(
+            "for cmd in open xdg-open cygstart; do ",
+            "command -v \"$cmd\" >/dev/null 2>&1 && exec \"$cmd\" \"$1\"; ",
+            "done; echo 'no opener found (tried open, xdg-open, cygstart)' >&2; exit 127"
+        );
+        spawn(&["sh", "-c", script, "sh", text], selection, context);
+    }
+}
+
+fn paste(config: &Config, text: &str, target_pane_id: Option<u32>) {
+    if let Some(id) = target_pane_id {
+        write_chars_to_pane_id(&paste_payload(config, text), PaneId::Terminal(id));
+    }
+}
+
+/// The characters `:paste:` types. The suffix stays outside the bracketed
+/// paste so a trailing newline actually submits the line.
+fn paste_payload(config: &Config, text: &str) -> String {
+    if config.paste_bracketed {
+        format!("\x1b[200~{}\x1b[201~{}", text, config.paste_suffix)
     } else {
-        // Try each opener directly without shell wrapper
-        // run_command is async so we fire all and let whichever exists succeed
-        run_command(&["open", text], context.clone());
-        run_command(&["xdg-open", text], context.clone());
-        run_command(&["cygstart", text], context);
+        format!("{}{}", text, config.paste_suffix)
+    }
+}
+
+/// Scroll the pane the match came from so the match is at the top, and
+/// enter scroll mode there.
+///
+/// The match's column picks the row of a soft-wrapped line that goes to the
+/// top (see `jump_offset`). Scroll mode has no cursor a plugin could move, so
+/// the column cannot be used beyond that.
+fn jump(selection: &Selection) {
+    let (Some(id), Some(offset)) = (selection.pane_id, selection.jump_offset) else {
+        return;
+    };
+    let pane_id = PaneId::Terminal(id);
+    focus_terminal_pane(id, false);
+    scroll_to_bottom_in_pane_id(pane_id);
+    let (pages, lines) = scroll_steps(offset, selection.pane_rows.unwrap_or(0));
+    for _ in 0..pages {
+        page_scroll_up_in_pane_id(pane_id);
+    }
+    for _ in 0..lines {
+        scroll_up_in_pane_id(pane_id);
+    }
+    switch_to_input_mode(&InputMode::Scroll);
+}
+
+/// Split scrolling `offset` lines into whole pages of `page_rows` and the
+/// lines left over, so a deep jump takes a few commands instead of one per
+/// line.
+fn scroll_steps(offset: usize, page_rows: usize) -> (usize, usize) {
+    match page_rows {
+        0 => (0, offset),
+        rows => (offset / rows, offset % rows),
+    }
+}
+
+/// Open a `path[:line[:column]]` match in `$EDITOR`. Relative paths are
+/// resolved against the pane's working directory. Zellij's open-file API
+/// takes no column, so the editor opens at the start of the line.
+fn edit(config: &Config, selection: &Selection) {
+    let (path, line_number, _column) = parse_file_location(&selection.text);
+    let file = FileToOpen {
+        path: PathBuf::from(path),
+        line_number,
+        cwd: selection.cwd.clone(),
+    };
+
+    // Tiled and in-place editors open relative to the focused pane
+    if config.edit_pane != "floating" {
+        if let Some(id) = selection.pane_id {
+            focus_terminal_pane(id, false);
+        }
+    }
+    match config.edit_pane.as_str() {
+        "tiled" => open_file(file, BTreeMap::new()),
+        "in-place" => open_file_in_place(file, BTreeMap::new()),
+        _ => open_file_floating(file, None, BTreeMap::new()),
     }
 }
 
-fn run_custom_action(action: &str, text: &str) {
-    let escaped = shell_escape(text);
-    let full_cmd = format!(
-        "HINT={} printf '%s' {} | {}",
-        escaped, escaped, action
+/// Split a `:pane:` action into its placement and command.
+fn pane_action(action: &str) -> Option<(&str, &str)> {
+    PANE_ACTIONS.iter().find_map(|placement| {
+        let command = action.strip_prefix(placement)?;
+        (command.is_empty() || command.starts_with(char::is_whitespace))
+            .then(|| (*placement, command.trim()))
+    })
+}
+
+/// Run a templated command in a new pane, in the working directory of the
+/// pane the match came from. Without a command, open a shell there.
+fn run_in_pane(placement: &str, command: &str, selection: &Selection) {
+    let command = if command.is_empty() {
+        "exec \"${SHELL:-sh}\"".to_string()
+    } else {
+        expand_template(command, selection)
+    };
+    let command = CommandToRun {
+        path: PathBuf::from("sh"),
+        args: vec![
+            "-c".to_string(),
+            format!("{} {}", exports(selection), command),
+        ],
+        cwd: selection.cwd.clone(),
+    };
+
+    match placement {
+        ":pane-tiled:" => {
+            // New tiled panes open next to the focused pane
+            if let Some(id) = selection.pane_id {
+                focus_terminal_pane(id, false);
+            }
+            open_command_pane(command, BTreeMap::new());
+        }
+        ":pane-tab:" => new_tabs_with_layout(&tab_layout(&command)),
+        _ => open_command_pane_floating(command, None, BTreeMap::new()),
+    }
+}
+
+/// A layout for a new tab holding just the command pane.
+fn tab_layout(command: &CommandToRun) -> String {
+    let cwd = command
+        .cwd
+        .as_ref()
+        .map(|cwd| format!(" cwd={}", kdl_string(&cwd.display().to_string())))
+        .unwrap_or_default();
+    let args: Vec<String> = command.args.iter().map(|arg| kdl_string(arg)).collect();
+    format!(
+        "layout {{\n    tab {{\n        pane command={}{} {{\n            args {}\n        }}\n    }}\n}}\n",
+        kdl_string(&command.path.display().to_string()),
+        cwd,
+        args.join(" ")
+    )
+}
+
+fn kdl_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Split off a trailing `:number`.
+fn split_number(s: &str) -> Option<(&str, usize)> {
+    let (head, tail) = s.rsplit_once(':')?;
+    tail.parse().ok().map(|n| (head, n))
+}
+
+/// Split `path:line:column` into its parts. Line and column are optional.
+fn parse_file_location(text: &str) -> (&str, Option<usize>, Option<usize>) {
+    match split_number(text) {
+        Some((rest, last)) => match split_number(rest) {
+            Some((path, line)) => (path, Some(line), Some(last)),
+            None => (rest, Some(last), None),
+        },
+        None => (text, None, None),
+    }
+}
+
+/// Replace placeholders such as `{match}`, `{line}` or a named group like
+/// `{file}` in `template` with shell-escaped values from the selection.
+/// Unknown placeholders and `${...}` are left alone, so shell parameter
+/// expansion and awk programs keep working.
+pub fn expand_template(template: &str, selection: &Selection) -> String {
+    let placeholder = Regex::new(r"(\$?)\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+    placeholder
+        .replace_all(template, |caps: &Captures<'_>| {
+            let value = if caps[1].is_empty() {
+                placeholder_value(&caps[2], selection)
+            } else {
+                None
+            };
+            value.unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+fn placeholder_value(name: &str, selection: &Selection) -> Option<String> {
+    let value = match name {
+        "match" => selection.text.clone(),
+        "pattern" => selection.pattern.clone().unwrap_or_default(),
+        "line" => selection
+            .position
+            .map_or_else(String::new, |(line, _)| (line + 1).to_string()),
+        "col" => selection
+            .position
+            .map_or_else(String::new, |(_, column)| (column + 1).to_string()),
+        "pane_id" => selection
+            .pane_id
+            .map_or_else(String::new, |id| id.to_string()),
+        "pane_cwd" => selection
+            .cwd
+            .as_ref()
+            .map_or_else(String::new, |cwd| cwd.display().to_string()),
+        "all" => {
+            let items: Vec<String> = selection
+                .items
+                .iter()
+                .map(|item| shell_escape(&item.text))
+                .collect();
+            return Some(items.join(" "));
+        }
+        _ => selection.groups.get(name)?.clone(),
+    };
+    Some(shell_value(&value))
+}
+
+fn run_custom_action(action: &str, selection: &Selection, context: BTreeMap<String, String>) {
+    spawn(
+        &["sh", "-c", &custom_command(action, selection)],
+        selection,
+        context,
     );
+}
 
-    let context = BTreeMap::new();
-    run_command(&["sh", "-c", &full_cmd], context);
+/// The shell command for a custom action: the selection piped into the
+/// expanded template, with the selection variables exported.
+fn custom_command(action: &str, selection: &Selection) -> String {
+    let action = expand_template(action, selection);
+    format!(
+        "{} {} | {}",
+        exports(selection),
+        printf_text(&selection.text),
+        action
+    )
+}
+
+/// A `printf` command writing `text` to stdout. Command arguments cannot
+/// hold a NUL, so the NULs of `multi_separator "nul"` go into the format
+/// string as `\000` escapes between the parts.
+fn printf_text(text: &str) -> String {
+    let parts: Vec<String> = text.split('\0').map(shell_escape).collect();
+    format!(
+        "printf '{}' {}",
+        vec!["%s"; parts.len()].join("\\000"),
+        parts.join(" ")
+    )
+}
+
+/// Shell statements exporting `HINT` and the other selection variables.
+/// Exported so the command itself sees them, not just the shell.
+fn exports(selection: &Selection) -> String {
+    let mut exports = format!("export HINT={};", shell_value(&selection.text));
+    if let Some(id) = selection.pane_id {
+        exports.push_str(&format!(" export PANE_ID={};", id));
+    }
+    // Multi-selected matches can come from different panes
+    let pane_ids: Option<Vec<String>> = selection
+        .items
+        .iter()
+        .map(|item| item.pane_id.map(|id| id.to_string()))
+        .collect();
+    if let (true, Some(ids)) = (selection.items.len() > 1, pane_ids) {
+        exports.push_str(&format!(
+            " export HINT_PANE_IDS={};",
+            shell_escape(&ids.join(" "))
+        ));
+    }
+    if let Some(ref pattern) = selection.pattern {
+        exports.push_str(&format!(" export HINT_PATTERN={};", shell_escape(pattern)));
+    }
+    if let Some((line, column)) = selection.position {
+        exports.push_str(&format!(
+            " export HINT_LINE={}; export HINT_COLUMN={};",
+            line + 1,
+            column + 1
+        ));
+    }
+    exports
 }
 
 fn shell_escape(s: &str) -> String {
     format!("'{}'", s.replace('\'', "'\\''"))
 }
+
+/// `s` shell-escaped for an argument or variable. Neither can hold a NUL, so
+/// NUL separators become newlines there; only stdin carries them as is.
+fn shell_value(s: &str) -> String {
+    shell_escape(&s.replace('\0', "\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection() -> Selection {
+        Selection {
+            text: "src/it's.rs:4".to_string(),
+            pattern: Some("loc".to_string()),
+            pane_id: Some(3),
+            position: Some((9, 0)),
+            jump_offset: None,
+            pane_rows: None,
+            cwd: Some(PathBuf::from("/work")),
+            groups: BTreeMap::from([
+                ("file".to_string(), "src/it's.rs".to_string()),
+                ("lnum".to_string(), "4".to_string()),
+            ]),
+            items: vec![
+                Item {
+                    text: "a b".to_string(),
+                    pane_id: Some(3),
+                },
+                Item {
+                    text: "c".to_string(),
+                    pane_id: Some(5),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn expands_placeholders_shell_escaped() {
+        let selection = selection();
+        assert_eq!(
+            expand_template("nvim +{lnum} {file}", &selection),
+            r"nvim +'4' 'src/it'\''s.rs'"
+        );
+        assert_eq!(
+            expand_template(
+                "echo {pattern} {line}:{col} {pane_id} {pane_cwd} {match}",
+                &selection
+            ),
+            r"echo 'loc' '10':'1' '3' '/work' 'src/it'\''s.rs:4'"
+        );
+        assert_eq!(
+            expand_template("printf '%s ' {all}", &selection),
+            "printf '%s ' 'a b' 'c'"
+        );
+    }
+
+    #[test]
+    fn absolutizes_relative_path_matches() {
+        let cwd = Path::new("/work");
+        assert_eq!(
+            absolute_path("src/main.rs:4", "file-line", cwd),
+            Some("/work/src/main.rs:4".to_string())
+        );
+        assert_eq!(
+            absolute_path("./a/b", "path", cwd),
+            Some("/work/a/b".to_string())
+        );
+        assert_eq!(absolute_path("/etc/hosts", "path", cwd), None);
+        assert_eq!(absolute_path("~/notes", "path", cwd), None);
+        assert_eq!(absolute_path("deadbeef", "sha", cwd), None);
+    }
+
+    #[test]
+    fn absolutizes_only_when_enabled() {
+        let mut config = Config::default();
+        let mut selection = selection();
+        selection.pattern = Some("file-line".to_string());
+        assert!(absolutize(&config, &selection).is_none());
+
+        config.absolute_paths = true;
+        let resolved = absolutize(&config, &selection).unwrap();
+        assert_eq!(resolved.text, "/work/src/it's.rs:4");
+        assert_eq!(resolved.items[0].text, "/work/src/it's.rs:4");
+    }
+
+    #[test]
+    fn absolutizes_multi_selected_items_against_their_pane() {
+        let mut config = Config::default();
+        let cwd = Some(Path::new("/work"));
+        assert_eq!(
+            absolutize_item(&config, "src/a.rs", "path", cwd),
+            "src/a.rs"
+        );
+
+        config.absolute_paths = true;
+        assert_eq!(
+            absolutize_item(&config, "src/a.rs", "path", cwd),
+            "/work/src/a.rs"
+        );
+        assert_eq!(
+            absolutize_item(&config, "src/a.rs", "path", None),
+            "src/a.rs"
+        );
+    }
+
+    #[test]
+    fn exports_the_pane_of_every_multi_selected_item() {
+        let mut selection = selection();
+        assert!(exports(&selection).contains("export HINT_PANE_IDS='3 5';"));
+
+        selection.items.truncate(1);
+        assert!(!exports(&selection).contains("HINT_PANE_IDS"));
+    }
+
+    #[test]
+    fn formats_multi_selections() {
+        let items = vec![
+            "a b".to_string(),
+            "it's".to_string(),
+            "q\"\u{1}".to_string(),
+        ];
+        let mut config = Config::default();
+        assert_eq!(format_multi(&config, &items), "a b it's q\"\u{1}");
+
+        config.multi_separator = "\n".to_string();
+        config.multi_format = "shell-quoted".to_string();
+        assert_eq!(
+            format_multi(&config, &items),
+            "'a b'\n'it'\\''s'\n'q\"\u{1}'"
+        );
+
+        config.multi_format = "json".to_string();
+        assert_eq!(
+            format_multi(&config, &items),
+            r#"["a b","it's","q\"\u0001"]"#
+        );
+    }
+
+    #[test]
+    fn pipes_nul_separated_matches_into_commands() {
+        let mut selection = selection();
+        selection.text = "a b\0it's".to_string();
+        let cmd = custom_command("cat; printf '|%s' \"$HINT\" {match}", &selection);
+        assert!(!cmd.contains('\0'));
+
+        let output = std::process::Command::new("sh")
+            .args(["-c", &cmd])
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"a b\0it's|a b\nit's|a b\nit's");
+    }
+
+    #[test]
+    fn parses_pane_actions() {
+        assert_eq!(
+            pane_action(":pane: git show {match}"),
+            Some((":pane:", "git show {match}"))
+        );
+        assert_eq!(pane_action(":pane-tab:"), Some((":pane-tab:", "")));
+        assert_eq!(
+            pane_action(":pane-tiled:  less"),
+            Some((":pane-tiled:", "less"))
+        );
+        assert_eq!(pane_action(":panes:"), None);
+        assert_eq!(pane_action(":copy:"), None);
+    }
+
+    #[test]
+    fn builds_a_tab_layout_for_the_command() {
+        let command = CommandToRun {
+            path: PathBuf::from("sh"),
+            args: vec!["-c".to_string(), r#"echo "a\b""#.to_string()],
+            cwd: Some(PathBuf::from("/work")),
+        };
+        assert_eq!(
+            tab_layout(&command),
+            concat!(
+                "layout {\n",
+                "    tab {\n",
+                "        pane command=\"sh\" cwd=\"/work\" {\n",
+                "            args \"-c\" \"echo \\\"a\\\\b\\\"\"\n",
+                "        }\n",
+                "    }\n",
+                "}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_placeholders_alone() {
+        let selection = selection();
+        assert_eq!(
+            expand_template("awk '{print $1}' ${HOME} {nope} ${match}", &selection),
+            "awk '{print $1}' ${HOME} {nope} ${match}"
+        );
+    }
+
+    #[test]
+    fn describes_action_results() {
+        let context = action_context(":copy:", "héllo", 0);
+        assert_eq!(
+            describe_result(Some(0), b"", &context),
+            Ok(Some("Copied 5 chars".to_string()))
+        );
+        assert_eq!(
+            describe_result(Some(1), b"xclip: Can't open display\n", &context),
+            Err("Action `:copy:` failed (exit code 1)\nxclip: Can't open display".to_string())
+        );
+
+        let context = action_context("my-script", "x", 1);
+        assert_eq!(describe_result(Some(0), b"", &context), Ok(None));
+        assert_eq!(result_step(&context), Some(1));
+        assert_eq!(
+            describe_result(None, b"", &context),
+            Err("Action `my-script` failed (killed)".to_string())
+        );
+        assert_eq!(osc52_fallback(&context), None);
+    }
+
+    #[test]
+    fn copy_falls_back_to_osc52_only_in_auto_mode() {
+        let mut context = action_context(":copy:", "hi", 0);
+        assert_eq!(osc52_fallback(&context), None);
+
+        context.insert(CONTEXT_TEXT.to_string(), "hi".to_string());
+        assert_eq!(
+            osc52_fallback(&context),
+            Some((
+                "\x1b]52;c;aGk=\x07".to_string(),
+                "Copied 2 chars".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn scrolls_by_pages_then_lines() {
+        assert_eq!(scroll_steps(2500, 40), (62, 20));
+        assert_eq!(scroll_steps(39, 40), (0, 39));
+        assert_eq!(scroll_steps(7, 0), (0, 7));
+    }
+
+    #[test]
+    fn parses_file_locations() {
+        assert_eq!(
+            parse_file_location("src/foo.rs:42:7"),
+            ("src/foo.rs", Some(42), Some(7))
+        );
+        assert_eq!(
+            parse_file_location("src/foo.rs:42"),
+            ("src/foo.rs", Some(42), None)
+        );
+        assert_eq!(
+            parse_file_location("src/foo.rs"),
+            ("src/foo.rs", None, None)
+        );
+        assert_eq!(parse_file_location("a:b.rs:3"), ("a:b.rs", Some(3), None));
+    }
+
+    #[test]
+    fn paste_payload_wraps_and_appends_suffix() {
+        let mut config = Config::default();
+        assert_eq!(paste_payload(&config, "abc"), "abc");
+
+        config.paste_suffix = " ".to_string();
+        assert_eq!(paste_payload(&config, "abc"), "abc ");
+
+        config.paste_bracketed = true;
+        config.paste_suffix = "\r".to_string();
+        assert_eq!(paste_payload(&config, "abc"), "\x1b[200~abc\x1b[201~\r");
+    }
+}
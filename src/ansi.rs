@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use unicode_width::UnicodeWidthChar;
+
 /// Converts tmux-style format strings (e.g. "fg=green,bold") directly to ANSI SGR escape sequences.
 /// No external process calls needed (unlike the Crystal version which used `tput`).
 const RESET: &str = "\x1b[0m";
@@ -114,6 +116,85 @@ pub fn format_style(input: &str) -> String {
     parse_style(cleaned)
 }
 
+/// Split formatted output into rows of at most `width` cells, as the
+/// terminal would soft-wrap it. Escape sequences take no space, and each new
+/// row re-applies the SGR state active where it starts so highlights carry
+/// over the split.
+pub fn split_rows(formatted: &str, width: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut row_width = 0;
+    let mut state = String::new();
+    let mut chars = formatted.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            let mut sequence = String::from(c);
+            if let Some(introducer) = chars.next_if_eq(&'[') {
+                sequence.push(introducer);
+                for c in chars.by_ref() {
+                    sequence.push(c);
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            if sequence.ends_with('m') {
+                if is_reset(&sequence) {
+                    state.clear();
+                }
+                state.push_str(&sequence);
+            }
+            row.push_str(&sequence);
+            continue;
+        }
+
+        let char_width = c.width().unwrap_or(0);
+        if width > 0 && row_width > 0 && row_width + char_width > width {
+            rows.push(std::mem::replace(&mut row, state.clone()));
+            row_width = 0;
+        }
+        row.push(c);
+        row_width += char_width;
+    }
+
+    rows.push(row);
+    rows
+}
+
+/// OSC 52 sequence asking the terminal emulator to put `text` on the system
+/// clipboard. Works through SSH, since it travels with the output.
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn is_reset(sequence: &str) -> bool {
+    let params = sequence.trim_start_matches("\x1b[").trim_end_matches('m');
+    matches!(params.split(';').next(), Some("") | Some("0"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,4 +258,35 @@ mod tests {
         let result = parse_style("");
         assert_eq!(result, "");
     }
+
+    #[test]
+    fn split_rows_wraps_at_width() {
+        assert_eq!(split_rows("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(split_rows("", 3), vec![""]);
+    }
+
+    #[test]
+    fn split_rows_carries_style_to_the_next_row() {
+        let rows = split_rows("a\x1b[33mbcd\x1b[0me", 3);
+        assert_eq!(rows, vec!["a\x1b[33mbc", "\x1b[33md\x1b[0me"]);
+    }
+
+    #[test]
+    fn split_rows_keeps_wide_chars_whole() {
+        assert_eq!(split_rows("a日本", 2), vec!["a", "日", "本"]);
+    }
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn osc52_wraps_base64_text() {
+        assert_eq!(osc52("hi"), "\x1b]52;c;aGk=\x07");
+    }
 }
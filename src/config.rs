@@ -2,8 +2,34 @@ use std::collections::{BTreeMap, HashMap};
 
 use crate::ansi;
 
+/// Which action a selection runs, picked by the modifier held while typing
+/// the last hint character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ActionKey {
+    Main,
+    Ctrl,
+    Alt,
+    Shift,
+}
+
+/// A named pattern: a builtin name such as `url`, or `pattern_N` (or the
+/// `pattern_N_name` given to it) for custom patterns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub name: String,
+    pub regex: String,
+}
+
 pub struct Config {
-    pub action: String,
+    /// Each action is a pipeline: steps run in order on the same selection.
+    pub action: Vec<String>,
+    pub ctrl_action: Option<Vec<String>>,
+    pub alt_action: Option<Vec<String>>,
+    pub shift_action: Option<Vec<String>>,
+    /// Actions that override the global ones for matches of one pattern.
+    pub pattern_actions: HashMap<(ActionKey, String), Vec<String>>,
+    /// Skip the remaining steps of a pipeline once one fails.
+    pub stop_on_failure: bool,
     pub hint_position: String,
     pub hint_style: String,
     pub highlight_style: String,
@@ -11,9 +37,28 @@ pub struct Config {
     pub selected_highlight_style: String,
     pub backdrop_style: String,
     pub clipboard_command: Option<String>,
+    /// "auto" (local tools, then OSC 52), "local" or "osc52".
+    pub clipboard_backend: String,
     pub open_command: Option<String>,
+    pub paste_bracketed: bool,
+    pub paste_suffix: String,
+    pub edit_pane: String,
+    /// Resolve relative path matches against the pane's working directory.
+    pub absolute_paths: bool,
+    pub show_copied_notification: bool,
+    /// What goes between multi-selected matches.
+    pub multi_separator: String,
+    /// "plain", "shell-quoted" or "json".
+    pub multi_format: String,
+    /// Order multi-selected matches by screen position instead of by
+    /// selection order.
+    pub multi_sort_by_position: bool,
+    pub all_panes: bool,
+    pub target_pane: String,
+    pub live_refresh_interval: Option<f64>,
+    pub timeout: f64,
     pub alphabet: Vec<String>,
-    pub patterns: Vec<String>,
+    pub patterns: Vec<Pattern>,
 }
 
 impl Default for Config {
@@ -22,7 +67,12 @@ impl Default for Config {
         let patterns = all_builtin_patterns();
 
         Self {
-            action: ":copy:".to_string(),
+            action: vec![":copy:".to_string()],
+            ctrl_action: Some(vec![":open:".to_string()]),
+            alt_action: None,
+            shift_action: Some(vec![":paste:".to_string()]),
+            pattern_actions: HashMap::new(),
+            stop_on_failure: false,
             hint_position: "left".to_string(),
             hint_style: ansi::format_style("fg=green,bold"),
             highlight_style: ansi::format_style("fg=yellow"),
@@ -30,7 +80,20 @@ impl Default for Config {
             selected_highlight_style: ansi::format_style("fg=blue"),
             backdrop_style: ansi::format_style("dim"),
             clipboard_command: None,
+            clipboard_backend: "auto".to_string(),
             open_command: None,
+            paste_bracketed: false,
+            paste_suffix: String::new(),
+            edit_pane: "floating".to_string(),
+            absolute_paths: false,
+            show_copied_notification: false,
+            multi_separator: " ".to_string(),
+            multi_format: "plain".to_string(),
+            multi_sort_by_position: false,
+            all_panes: false,
+            target_pane: "focused".to_string(),
+            live_refresh_interval: None,
+            timeout: 10.0,
             alphabet,
             patterns,
         }
@@ -54,11 +117,16 @@ impl Config {
         let mut patterns = resolve_builtin_patterns(&enabled_builtin_patterns);
 
         // Collect user patterns (pattern_0, pattern_1, ...)
-        let mut user_patterns = Vec::new();
         for i in 0..20 {
             if let Some(p) = config.get(&format!("pattern_{i}")) {
-                user_patterns.push(p.clone());
-                patterns.push(p.clone());
+                let name = config
+                    .get(&format!("pattern_{i}_name"))
+                    .cloned()
+                    .unwrap_or_else(|| format!("pattern_{i}"));
+                patterns.push(Pattern {
+                    name,
+                    regex: p.clone(),
+                });
             }
         }
 
@@ -88,10 +156,14 @@ impl Config {
             .unwrap_or_default();
 
         Self {
-            action: config
-                .get("action")
-                .cloned()
-                .unwrap_or_else(|| ":copy:".to_string()),
+            action: configured_action(config, "main_action")
+                .or_else(|| configured_action(config, "action"))
+                .unwrap_or_else(|| vec![":copy:".to_string()]),
+            ctrl_action: modifier_action(config, "ctrl_action", ":open:"),
+            alt_action: modifier_action(config, "alt_action", ""),
+            shift_action: modifier_action(config, "shift_action", ":paste:"),
+            pattern_actions: pattern_actions(config),
+            stop_on_failure: config.get("stop_on_failure").is_some_and(|v| v == "true"),
             hint_position: config
                 .get("hint_position")
                 .cloned()
@@ -101,15 +173,195 @@ impl Config {
             selected_hint_style,
             selected_highlight_style,
             backdrop_style,
-            clipboard_command: config.get("clipboard_command").cloned(),
+            clipboard_command: config
+                .get("clipboard_command")
+                .filter(|cmd| *cmd != "osc52")
+                .cloned(),
+            clipboard_backend: if config
+                .get("clipboard_command")
+                .is_some_and(|cmd| cmd == "osc52")
+            {
+                "osc52".to_string()
+            } else {
+                config
+                    .get("clipboard_backend")
+                    .cloned()
+                    .unwrap_or_else(|| "auto".to_string())
+            },
             open_command: config.get("open_command").cloned(),
+            paste_bracketed: config.get("paste_bracketed").is_some_and(|v| v == "true"),
+            paste_suffix: paste_suffix(config.get("paste_suffix").map(String::as_str)),
+            edit_pane: config
+                .get("edit_pane")
+                .cloned()
+                .unwrap_or_else(|| "floating".to_string()),
+            absolute_paths: config.get("absolute_paths").is_some_and(|v| v == "true"),
+            show_copied_notification: config
+                .get("show_copied_notification")
+                .is_some_and(|v| v == "true"),
+            multi_separator: multi_separator(config.get("multi_separator").map(String::as_str)),
+            multi_format: config
+                .get("multi_format")
+                .cloned()
+                .unwrap_or_else(|| "plain".to_string()),
+            multi_sort_by_position: config.get("multi_order").is_some_and(|v| v == "position"),
+            all_panes: config.get("all_panes").is_some_and(|v| v == "true"),
+            target_pane: config
+                .get("target_pane")
+                .cloned()
+                .unwrap_or_else(|| "focused".to_string()),
+            live_refresh_interval: config
+                .get("live_refresh_interval")
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|secs| *secs > 0.0),
+            timeout: config
+                .get("timeout")
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|secs| *secs > 0.0)
+                .unwrap_or(10.0),
             alphabet,
             patterns,
         }
     }
 }
 
-fn resolve_builtin_patterns(enabled: &str) -> Vec<String> {
+impl Config {
+    /// The action to run for a selection of a `pattern` match made with
+    /// `key`. The pattern's own action for that key wins, then the global
+    /// modifier action, then the pattern's main action. Modifiers without an
+    /// action of their own fall back to the main action.
+    pub fn action_for(&self, key: ActionKey, pattern: Option<&str>) -> &[String] {
+        let pattern_action =
+            |key| pattern.and_then(|name| self.pattern_actions.get(&(key, name.to_string())));
+        let modifier_action = match key {
+            ActionKey::Main => None,
+            ActionKey::Ctrl => self.ctrl_action.as_ref(),
+            ActionKey::Alt => self.alt_action.as_ref(),
+            ActionKey::Shift => self.shift_action.as_ref(),
+        };
+        pattern_action(key)
+            .or(modifier_action)
+            .or_else(|| pattern_action(ActionKey::Main))
+            .unwrap_or(&self.action)
+    }
+
+    /// Check that every pattern compiles on its own, so a bad custom pattern
+    /// is reported by name instead of failing the combined regex.
+    pub fn validate_patterns(&self) -> Result<(), String> {
+        for pattern in &self.patterns {
+            if let Err(err) = regex::Regex::new(&pattern.regex) {
+                return Err(format!(
+                    "Invalid pattern {} `{}`: {err}",
+                    pattern.name, pattern.regex
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A modifier action, where an empty value means "use the main action".
+fn modifier_action(
+    config: &BTreeMap<String, String>,
+    key: &str,
+    default: &str,
+) -> Option<Vec<String>> {
+    let steps = configured_action(config, key).unwrap_or_else(|| parse_pipeline(default));
+    (!steps.is_empty()).then_some(steps)
+}
+
+/// The pipeline configured for `key`: numbered steps (`key_0`, `key_1`,
+/// ...) if there are any, otherwise the comma-separated `key` itself.
+fn configured_action(config: &BTreeMap<String, String>, key: &str) -> Option<Vec<String>> {
+    let numbered: Vec<String> = (0..10)
+        .filter_map(|i| config.get(&format!("{key}_{i}")))
+        .flat_map(|step| parse_pipeline(step))
+        .collect();
+    if numbered.is_empty() {
+        config.get(key).map(|value| parse_pipeline(value))
+    } else {
+        Some(numbered)
+    }
+}
+
+/// Split an action such as `:copy:,:paste:` into its steps. A comma only
+/// separates steps next to a builtin action, so custom commands can still
+/// contain commas.
+pub fn parse_pipeline(value: &str) -> Vec<String> {
+    let builtin = regex::Regex::new(r"^:[a-z][a-z-]*:").unwrap();
+    let mut steps: Vec<String> = Vec::new();
+    for part in value.split(',') {
+        let starts_step = steps.is_empty()
+            || builtin.is_match(part.trim_start())
+            || steps.last().is_some_and(|step| {
+                let step = step.trim();
+                builtin.find(step).is_some_and(|m| m.len() == step.len())
+            });
+        match steps.last_mut() {
+            Some(step) if !starts_step => {
+                step.push(',');
+                step.push_str(part);
+            }
+            _ => steps.push(part.to_string()),
+        }
+    }
+    steps
+        .into_iter()
+        .map(|step| step.trim().to_string())
+        .filter(|step| !step.is_empty())
+        .collect()
+}
+
+/// Per-pattern actions such as `action_url` or `ctrl_action_pattern_0`.
+/// Numbered keys like `action_0` are pipeline steps, not patterns.
+fn pattern_actions(config: &BTreeMap<String, String>) -> HashMap<(ActionKey, String), Vec<String>> {
+    const PREFIXES: [(&str, ActionKey); 5] = [
+        ("main_action_", ActionKey::Main),
+        ("action_", ActionKey::Main),
+        ("ctrl_action_", ActionKey::Ctrl),
+        ("alt_action_", ActionKey::Alt),
+        ("shift_action_", ActionKey::Shift),
+    ];
+
+    let mut actions = HashMap::new();
+    for (key, action) in config {
+        if action.is_empty() {
+            continue;
+        }
+        for (prefix, action_key) in PREFIXES {
+            match key.strip_prefix(prefix) {
+                Some(pattern) if !pattern.bytes().all(|b| b.is_ascii_digit()) => {
+                    actions.insert((action_key, pattern.to_string()), parse_pipeline(action));
+                }
+                _ => {}
+            }
+        }
+    }
+    actions
+}
+
+/// What goes between multi-selected matches: a named separator or the
+/// string itself.
+fn multi_separator(value: Option<&str>) -> String {
+    match value {
+        None | Some("space") => " ".to_string(),
+        Some("newline") => "\n".to_string(),
+        Some("comma") => ",".to_string(),
+        Some("nul") => "\0".to_string(),
+        Some(custom) => custom.to_string(),
+    }
+}
+
+/// What `:paste:` types after the selection: nothing, a space, or Enter.
+fn paste_suffix(value: Option<&str>) -> String {
+    match value {
+        Some("space") => " ".to_string(),
+        Some("newline") => "\r".to_string(),
+        _ => String::new(),
+    }
+}
+
+fn resolve_builtin_patterns(enabled: &str) -> Vec<Pattern> {
     if enabled == "all" {
         return all_builtin_patterns();
     }
@@ -117,12 +369,43 @@ fn resolve_builtin_patterns(enabled: &str) -> Vec<String> {
     let builtins = builtin_patterns();
     enabled
         .split(',')
-        .filter_map(|name| builtins.get(name.trim()).cloned())
+        .filter_map(|name| {
+            let name = name.trim();
+            builtins.get(name).map(|regex| Pattern {
+                name: name.to_string(),
+                regex: regex.clone(),
+            })
+        })
         .collect()
 }
 
-pub fn all_builtin_patterns() -> Vec<String> {
-    builtin_patterns().values().cloned().collect()
+/// Builtin pattern names, most specific first. The patterns are combined
+/// into one alternation where the first one matching at a position wins, so
+/// `sha` must not cut a UUID short and `digit` must not cut a SHA short.
+const BUILTIN_PRIORITY: [&str; 12] = [
+    "url",
+    "uuid",
+    "ip",
+    "file-line",
+    "git-status",
+    "git-status-branch",
+    "diff",
+    "path",
+    "sha",
+    "hex",
+    "kubernetes",
+    "digit",
+];
+
+pub fn all_builtin_patterns() -> Vec<Pattern> {
+    let builtins = builtin_patterns();
+    BUILTIN_PRIORITY
+        .iter()
+        .map(|name| Pattern {
+            name: name.to_string(),
+            regex: builtins[name].clone(),
+        })
+        .collect()
 }
 
 pub fn builtin_patterns() -> HashMap<&'static str, String> {
@@ -143,6 +426,10 @@ pub fn builtin_patterns() -> HashMap<&'static str, String> {
             r"(([.\w\-~\$@]+)?(/[.\w\-@]+)+/?)".to_string(),
         ),
         ("hex", r"(0x[0-9a-fA-F]+)".to_string()),
+        (
+            "file-line",
+            r"(?:[\w\-.~@]*/)*[\w\-.@]+\.[A-Za-z]\w*:\d+(?::\d+)?".to_string(),
+        ),
         (
             "kubernetes",
             concat!(
@@ -329,6 +616,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn file_line_matches() {
+        let input = "
+      src/foo.rs:42:7: error
+      at ./lib/a.py:3 in main
+      /abs/path/Makefile.am:10
+      not 10.0.0.1:8080 or 12:30
+      ";
+        assert_eq!(
+            matches_for("file-line", input),
+            vec![
+                "src/foo.rs:42:7",
+                "./lib/a.py:3",
+                "/abs/path/Makefile.am:10"
+            ]
+        );
+    }
+
     #[test]
     fn hex_matches() {
         let input = "
@@ -415,7 +720,7 @@ Changes to be committed:
     #[test]
     fn from_kdl_defaults() {
         let config = Config::from_kdl(&BTreeMap::new());
-        assert_eq!(config.action, ":copy:");
+        assert_eq!(config.action, [":copy:"]);
         assert_eq!(config.hint_position, "left");
         assert!(!config.patterns.is_empty());
     }
@@ -428,7 +733,140 @@ Changes to be committed:
         map.insert("pattern_0".to_string(), r"\bfoo\b".to_string());
 
         let config = Config::from_kdl(&map);
-        assert_eq!(config.action, ":open:");
-        assert!(config.patterns.iter().any(|p| p == r"\bfoo\b"));
+        assert_eq!(config.action, [":open:"]);
+        assert!(config
+            .patterns
+            .iter()
+            .any(|p| p.name == "pattern_0" && p.regex == r"\bfoo\b"));
+    }
+
+    #[test]
+    fn builtin_priority_lists_every_builtin_once() {
+        let mut names: Vec<&str> = builtin_patterns().into_keys().collect();
+        let mut priority = BUILTIN_PRIORITY.to_vec();
+        names.sort_unstable();
+        priority.sort_unstable();
+        assert_eq!(priority, names);
+    }
+
+    #[test]
+    fn validate_patterns_reports_invalid_pattern() {
+        let mut map = BTreeMap::new();
+        map.insert("pattern_0".to_string(), "foo(".to_string());
+
+        let err = Config::from_kdl(&map).validate_patterns().unwrap_err();
+        assert!(err.starts_with("Invalid pattern pattern_0 `foo(`"));
+        assert!(Config::default().validate_patterns().is_ok());
+    }
+
+    #[test]
+    fn modifier_actions_fall_back_to_the_main_action() {
+        let config = Config::default();
+        assert_eq!(config.action_for(ActionKey::Main, None), [":copy:"]);
+        assert_eq!(config.action_for(ActionKey::Ctrl, None), [":open:"]);
+        assert_eq!(config.action_for(ActionKey::Shift, None), [":paste:"]);
+        assert_eq!(config.action_for(ActionKey::Alt, None), [":copy:"]);
+
+        let mut map = BTreeMap::new();
+        map.insert("main_action".to_string(), "xargs echo".to_string());
+        map.insert("alt_action".to_string(), ":open:".to_string());
+        map.insert("shift_action".to_string(), "".to_string());
+
+        let config = Config::from_kdl(&map);
+        assert_eq!(config.action_for(ActionKey::Main, None), ["xargs echo"]);
+        assert_eq!(config.action_for(ActionKey::Alt, None), [":open:"]);
+        assert_eq!(config.action_for(ActionKey::Shift, None), ["xargs echo"]);
+    }
+
+    #[test]
+    fn pattern_actions_override_global_actions() {
+        let mut map = BTreeMap::new();
+        map.insert("action_url".to_string(), ":open:".to_string());
+        map.insert("ctrl_action_sha".to_string(), "git show".to_string());
+        map.insert("pattern_0".to_string(), r"JIRA-\d+".to_string());
+        map.insert("pattern_0_name".to_string(), "jira".to_string());
+        map.insert("main_action_jira".to_string(), "open-ticket".to_string());
+
+        let config = Config::from_kdl(&map);
+        assert_eq!(config.patterns.last().unwrap().name, "jira");
+        assert_eq!(config.action_for(ActionKey::Main, Some("url")), [":open:"]);
+        assert_eq!(config.action_for(ActionKey::Main, Some("sha")), [":copy:"]);
+        assert_eq!(
+            config.action_for(ActionKey::Ctrl, Some("sha")),
+            ["git show"]
+        );
+        assert_eq!(
+            config.action_for(ActionKey::Main, Some("jira")),
+            ["open-ticket"]
+        );
+        // An explicit global modifier action beats the pattern's main action
+        assert_eq!(
+            config.action_for(ActionKey::Shift, Some("url")),
+            [":paste:"]
+        );
+        assert_eq!(config.action_for(ActionKey::Alt, Some("url")), [":open:"]);
+    }
+
+    #[test]
+    fn splits_pipelines_next_to_builtin_actions() {
+        assert_eq!(parse_pipeline(":copy:,:paste:"), [":copy:", ":paste:"]);
+        assert_eq!(
+            parse_pipeline(":copy: , git show {match}"),
+            [":copy:", "git show {match}"]
+        );
+        assert_eq!(
+            parse_pipeline("cut -d, -f1,:open:"),
+            ["cut -d, -f1", ":open:"]
+        );
+        assert_eq!(
+            parse_pipeline("awk -F, '{print $2}'"),
+            ["awk -F, '{print $2}'"]
+        );
+        assert!(parse_pipeline("").is_empty());
+    }
+
+    #[test]
+    fn numbered_actions_form_a_pipeline() {
+        let mut map = BTreeMap::new();
+        map.insert("action".to_string(), ":open:".to_string());
+        map.insert("action_0".to_string(), ":copy:".to_string());
+        map.insert("action_1".to_string(), "echo a,b".to_string());
+        map.insert("ctrl_action_0".to_string(), ":paste:".to_string());
+        map.insert("action_url".to_string(), ":copy:,:open:".to_string());
+
+        let config = Config::from_kdl(&map);
+        assert_eq!(
+            config.action_for(ActionKey::Main, None),
+            [":copy:", "echo a,b"]
+        );
+        assert_eq!(config.action_for(ActionKey::Ctrl, None), [":paste:"]);
+        assert_eq!(
+            config.action_for(ActionKey::Main, Some("url")),
+            [":copy:", ":open:"]
+        );
+        assert!(!config
+            .pattern_actions
+            .contains_key(&(ActionKey::Main, "0".to_string())));
+        assert!(!config.stop_on_failure);
+    }
+
+    #[test]
+    fn multi_separator_names_and_custom_strings() {
+        assert_eq!(multi_separator(None), " ");
+        assert_eq!(multi_separator(Some("newline")), "\n");
+        assert_eq!(multi_separator(Some("comma")), ",");
+        assert_eq!(multi_separator(Some("nul")), "\0");
+        assert_eq!(multi_separator(Some(" | ")), " | ");
+    }
+
+    #[test]
+    fn clipboard_command_can_select_osc52() {
+        let mut map = BTreeMap::new();
+        assert_eq!(Config::from_kdl(&map).clipboard_backend, "auto");
+
+        map.insert("clipboard_command".to_string(), "osc52".to_string());
+        let config = Config::from_kdl(&map);
+        assert_eq!(config.clipboard_backend, "osc52");
+        assert_eq!(config.clipboard_command, None);
     }
 }
@@ -1,16 +1,30 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
 
 use regex::Regex;
 use unicode_width::UnicodeWidthStr;
 
-use crate::config::Config;
+use crate::config::{Config, Pattern};
 use crate::huffman;
 use crate::match_formatter::MatchFormatter;
 
+/// A match as the index of the pane it was captured from and its text.
+/// Equal texts in different panes are different matches.
+pub type MatchKey = (usize, String);
+
 #[derive(Clone, Debug)]
 pub struct Target {
     pub text: String,
     pub hint: String,
+    /// Index of the input line the match was found on.
+    pub line: usize,
+    /// Display columns the captured text spans on its line.
+    pub columns: Range<usize>,
+    /// Name of the pattern that produced the match.
+    pub pattern: String,
+    /// Named groups of the pattern that took part in the match, other than
+    /// `match`.
+    pub groups: BTreeMap<String, String>,
 }
 
 pub struct FormattedLine {
@@ -22,11 +36,27 @@ pub struct Hinter {
     width: usize,
     formatter: MatchFormatter,
     pattern: Regex,
+    alphabet: Vec<String>,
+    hint_pool: Vec<String>,
     hints: Vec<String>,
+    pinned: HashMap<MatchKey, String>,
+    unique_texts: HashSet<MatchKey>,
     target_by_hint: HashMap<String, Target>,
-    target_by_text: HashMap<String, Target>,
+    target_by_text: HashMap<MatchKey, Target>,
     reuse_hints: bool,
-    match_group_indices: Vec<usize>,
+    /// First input line of each captured pane.
+    pane_starts: Vec<usize>,
+    pattern_names: Vec<String>,
+    pattern_groups: Vec<PatternGroups>,
+}
+
+/// Capture group indices of one pattern within the combined regex.
+#[derive(Clone, Default)]
+struct PatternGroups {
+    wrapper: usize,
+    match_group: Option<usize>,
+    /// Other named groups, by their name in the pattern.
+    named: Vec<(String, usize)>,
 }
 
 impl Hinter {
@@ -50,7 +80,7 @@ impl Hinter {
     pub fn with_options(
         input: &[String],
         width: usize,
-        patterns: &[String],
+        patterns: &[Pattern],
         alphabet: &[String],
         hint_position: String,
         hint_style: String,
@@ -60,27 +90,24 @@ impl Hinter {
         backdrop_style: String,
         reuse_hints: bool,
     ) -> Self {
-        // Rename (?P<match>...) groups to unique names per pattern to avoid
-        // "duplicate capture group name" errors in the regex crate
+        // Prefix named groups such as (?P<match>...) with the pattern index to
+        // avoid "duplicate capture group name" errors in the regex crate, and
+        // wrap each pattern in a group so we know which one matched
+        let group_name = Regex::new(r"\(\?P?<([A-Za-z_][A-Za-z0-9_]*)>").unwrap();
         let renamed: Vec<String> = patterns
             .iter()
             .enumerate()
-            .map(|(i, p)| p.replace("(?P<match>", &format!("(?P<match_{i}>")))
+            .map(|(i, p)| {
+                let regex = group_name.replace_all(&p.regex, format!("(?P<p{i}_$1>"));
+                format!("(?P<p{i}>{regex})")
+            })
             .collect();
-        let combined = format!("({})", renamed.join("|"));
+        let combined = renamed.join("|");
         let pattern = Regex::new(&combined).expect("Invalid regex pattern");
 
-        let match_group_indices = find_match_group_indices(&pattern);
-
-        let n_matches = if reuse_hints {
-            count_unique_matches(input, &pattern, &match_group_indices)
-        } else {
-            count_matches(input, &pattern)
-        };
-
-        let hints = huffman::generate_hints(alphabet, n_matches);
+        let pattern_groups = find_pattern_groups(&pattern, patterns.len());
 
-        Self {
+        let mut hinter = Self {
             lines: input.to_vec(),
             width,
             formatter: MatchFormatter {
@@ -92,47 +119,186 @@ impl Hinter {
                 hint_position,
             },
             pattern,
-            hints,
+            alphabet: alphabet.to_vec(),
+            hint_pool: Vec::new(),
+            hints: Vec::new(),
+            pinned: HashMap::new(),
+            unique_texts: HashSet::new(),
             target_by_hint: HashMap::new(),
             target_by_text: HashMap::new(),
             reuse_hints,
-            match_group_indices,
+            pane_starts: vec![0],
+            pattern_names: patterns.iter().map(|p| p.name.clone()).collect(),
+            pattern_groups,
+        };
+        hinter.index_matches();
+        hinter
+    }
+
+    /// Treat the input as the captures of several panes, starting at the
+    /// given lines. Equal texts only share a hint within one pane, so every
+    /// target knows the pane it came from.
+    pub fn with_pane_starts(mut self, starts: &[usize]) -> Self {
+        self.pane_starts = starts.to_vec();
+        if self.pane_starts.first() != Some(&0) {
+            self.pane_starts.insert(0, 0);
         }
+        self.index_matches();
+        self
     }
 
+    /// Collect the distinct matches and generate enough hints for them.
+    fn index_matches(&mut self) {
+        let mut seen = HashSet::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            for caps in self.pattern.captures_iter(line) {
+                let (_, text, _) = captured_text_and_offset(&caps, &self.pattern_groups);
+                seen.insert((self.pane_of(i), text));
+            }
+        }
+        self.unique_texts = seen;
+        let n_matches = if self.reuse_hints {
+            self.unique_texts.len()
+        } else {
+            count_matches(&self.lines, &self.pattern)
+        };
+        self.hint_pool = huffman::generate_hints(&self.alphabet, n_matches);
+        self.hints = self.hint_pool.clone();
+    }
+
+    /// Index of the pane `line` was captured from.
+    pub fn pane_of(&self, line: usize) -> usize {
+        self.pane_starts
+            .partition_point(|&start| start <= line)
+            .saturating_sub(1)
+    }
+
+    /// Format the lines in `viewport`.
+    ///
+    /// Hints are assigned over the whole input, but lines outside the
+    /// viewport are processed first so the visible matches receive the
+    /// shortest hints. Matches in `selected` are styled as picked, whatever
+    /// hint they get this time.
     pub fn run(
         &mut self,
         input_prefix: &str,
-        selected_hints: &[String],
+        selected: &[MatchKey],
         render_width: usize,
+        viewport: Range<usize>,
     ) -> Vec<FormattedLine> {
+        let width = if render_width > 0 { render_width } else { self.width };
+        self.run_regions(input_prefix, selected, &[(viewport, width)])
+            .pop()
+            .unwrap_or_default()
+    }
+
+    /// Format several visible regions of the input, each padded to its own
+    /// width, sharing a single hint space.
+    pub fn run_regions(
+        &mut self,
+        input_prefix: &str,
+        selected: &[MatchKey],
+        regions: &[(Range<usize>, usize)],
+    ) -> Vec<Vec<FormattedLine>> {
         self.regenerate_hints();
 
-        let width = if render_width > 0 { render_width } else { self.width };
         let lines = self.lines.clone();
-        let mut result = Vec::new();
-        for line in &lines {
-            let formatted = self.process_line(line, input_prefix, selected_hints, width);
-            result.push(formatted);
+        let regions: Vec<(Range<usize>, usize)> = regions
+            .iter()
+            .map(|(range, width)| {
+                let end = range.end.min(lines.len());
+                (range.start.min(end)..end, *width)
+            })
+            .collect();
+
+        for (i, line) in lines.iter().enumerate() {
+            if !regions.iter().any(|(range, _)| range.contains(&i)) {
+                self.process_line(i, line, input_prefix, selected, self.width);
+            }
         }
-        result
+
+        regions
+            .iter()
+            .map(|(range, width)| {
+                range
+                    .clone()
+                    .map(|i| self.process_line(i, &lines[i], input_prefix, selected, *width))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Number of distinct matches in the input.
+    pub fn match_count(&self) -> usize {
+        self.unique_texts.len()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Number of physical rows `line` takes up when soft-wrapped at `width`.
+    pub fn row_count(&self, line: usize, width: usize) -> usize {
+        self.lines
+            .get(line)
+            .map_or(0, |l| rows_for_width(display_width(l), width))
+    }
+
+    /// Physical rows at `width` below the row holding `column` of `line`,
+    /// up to the end of the input lines before `end`.
+    pub fn rows_below(&self, line: usize, column: usize, end: usize, width: usize) -> usize {
+        let rows_after: usize = (line + 1..end).map(|l| self.row_count(l, width)).sum();
+        let row_in_line = column.checked_div(width).unwrap_or(0);
+        rows_after + self.row_count(line, width).saturating_sub(row_in_line + 1)
+    }
+
+    /// Physical rows at `width` above the row holding `column` of `line`,
+    /// from the input line `start` on.
+    pub fn rows_above(&self, line: usize, column: usize, start: usize, width: usize) -> usize {
+        let rows_before: usize = (start..line).map(|l| self.row_count(l, width)).sum();
+        rows_before + column.checked_div(width).unwrap_or(0)
     }
 
     pub fn lookup(&self, hint: &str) -> Option<&Target> {
         self.target_by_hint.get(hint)
     }
 
+    /// Keep the hints `previous` showed for matches that are still present,
+    /// so a refreshed capture does not move hints the user may be reading.
+    /// New matches get hints that do not clash with the kept ones. Returns
+    /// `false` when there is no room for that; the hints are not kept then.
+    pub fn keep_hints_from(&mut self, previous: &Hinter) -> bool {
+        let pinned: HashMap<MatchKey, String> = previous
+            .target_by_text
+            .iter()
+            .filter(|(key, _)| self.unique_texts.contains(*key))
+            .map(|(key, target)| (key.clone(), target.hint.clone()))
+            .collect();
+        let taken: Vec<String> = pinned.values().cloned().collect();
+        let needed = self.unique_texts.len() - pinned.len();
+
+        match huffman::generate_hints_avoiding(&self.alphabet, needed, &taken) {
+            Some(pool) => {
+                self.hint_pool = pool;
+                self.pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
     fn regenerate_hints(&mut self) {
-        // Only clear mappings; hints were generated in constructor
+        self.hints = self.hint_pool.clone();
         self.target_by_hint.clear();
         self.target_by_text.clear();
     }
 
     fn process_line(
         &mut self,
+        line_index: usize,
         line: &str,
         input_prefix: &str,
-        selected_hints: &[String],
+        selected: &[MatchKey],
         width: usize,
     ) -> FormattedLine {
         let tab_positions = tab_positions_for(line);
@@ -143,7 +309,7 @@ impl Hinter {
 
         // Clone pattern to avoid borrow issues
         let pattern = self.pattern.clone();
-        let match_group_indices = self.match_group_indices.clone();
+        let pattern_groups = self.pattern_groups.clone();
 
         for caps in pattern.captures_iter(line) {
             let whole_match = caps.get(0).unwrap();
@@ -155,10 +321,14 @@ impl Hinter {
             result.push_str(&line[last_end..match_start]);
 
             // Get captured text (named group "match" or whole match)
-            let (captured_text, relative_offset) =
-                captured_text_and_offset(&caps, &match_group_indices);
+            let (pattern_index, captured_text, relative_offset) =
+                captured_text_and_offset(&caps, &pattern_groups);
+            let groups = named_groups(&caps, pattern_groups.get(pattern_index));
 
-            let hint = self.hint_for_text(&captured_text);
+            let key = (self.pane_of(line_index), captured_text);
+            let hint = self.hint_for(&key);
+            let is_selected = selected.contains(&key);
+            let (pane, captured_text) = key;
 
             // If hint is longer than captured text, skip this match
             if hint.chars().count() > captured_text.chars().count() {
@@ -168,7 +338,18 @@ impl Hinter {
                 continue;
             }
 
-            self.build_target(&captured_text, &hint);
+            let captured_start = match_start + relative_offset.map_or(0, |(start, _)| start);
+            let first_column = line[..captured_start].width();
+            let columns = first_column..first_column + captured_text.width();
+            self.build_target(
+                pane,
+                &captured_text,
+                &hint,
+                line_index,
+                columns,
+                pattern_index,
+                groups,
+            );
 
             // If there's input and hint doesn't start with it, show original text
             if !input_prefix.is_empty() && !hint.starts_with(input_prefix) {
@@ -177,12 +358,9 @@ impl Hinter {
                 continue;
             }
 
-            let formatted = self.formatter.format(
-                &hint,
-                match_text,
-                selected_hints.contains(&hint),
-                relative_offset,
-            );
+            let formatted = self
+                .formatter
+                .format(&hint, match_text, is_selected, relative_offset);
             result.push_str(&formatted);
             last_end = match_end;
         }
@@ -191,19 +369,15 @@ impl Hinter {
         result.push_str(&line[last_end..]);
 
         // Tab expansion
-        let initial_len = result.len();
         result = expand_tabs(&result, &tab_positions);
-        let tab_correction = result.len() - initial_len;
 
         // Prepend backdrop style
         let backdrop = &self.formatter.backdrop_style;
         let with_backdrop = format!("{}{}", backdrop, result);
 
-        // Calculate padding
-        let display_width = UnicodeWidthStr::width(line);
-        let padding_amount = width
-            .saturating_sub(display_width)
-            .saturating_sub(tab_correction);
+        // Pad up to the end of the last row the line wraps onto
+        let rows = rows_for_width(display_width(line), width);
+        let padding_amount = (rows * width).saturating_sub(display_width(line));
         let padding = " ".repeat(padding_amount);
 
         FormattedLine {
@@ -211,9 +385,12 @@ impl Hinter {
         }
     }
 
-    fn hint_for_text(&mut self, text: &str) -> String {
+    fn hint_for(&mut self, key: &MatchKey) -> String {
+        if let Some(hint) = self.pinned.get(key) {
+            return hint.clone();
+        }
         if self.reuse_hints {
-            if let Some(target) = self.target_by_text.get(text) {
+            if let Some(target) = self.target_by_text.get(key) {
                 return target.hint.clone();
             }
         }
@@ -224,45 +401,109 @@ impl Hinter {
         self.hints.pop().unwrap_or_default()
     }
 
-    fn build_target(&mut self, text: &str, hint: &str) {
+    #[allow(clippy::too_many_arguments)]
+    fn build_target(
+        &mut self,
+        pane: usize,
+        text: &str,
+        hint: &str,
+        line: usize,
+        columns: Range<usize>,
+        pattern_index: usize,
+        groups: BTreeMap<String, String>,
+    ) {
         let target = Target {
             text: text.to_string(),
             hint: hint.to_string(),
+            line,
+            columns,
+            pattern: self
+                .pattern_names
+                .get(pattern_index)
+                .cloned()
+                .unwrap_or_default(),
+            groups,
         };
         self.target_by_hint.insert(hint.to_string(), target.clone());
-        self.target_by_text.insert(text.to_string(), target);
+        self.target_by_text.insert((pane, text.to_string()), target);
     }
 }
 
-fn find_match_group_indices(pattern: &Regex) -> Vec<usize> {
-    pattern
-        .capture_names()
-        .enumerate()
-        .filter_map(|(i, name)| {
-            if let Some(n) = name {
-                if n.starts_with("match_") {
-                    return Some(i);
-                }
-            }
-            None
+/// Map the `p{i}` and `p{i}_{name}` groups of the combined regex back to
+/// the patterns they came from.
+fn find_pattern_groups(pattern: &Regex, n_patterns: usize) -> Vec<PatternGroups> {
+    let mut groups = vec![PatternGroups::default(); n_patterns];
+    for (index, name) in pattern.capture_names().enumerate() {
+        let Some(name) = name.and_then(|n| n.strip_prefix('p')) else {
+            continue;
+        };
+        let (pattern_index, group) = match name.split_once('_') {
+            Some((i, group)) => (i, Some(group)),
+            None => (name, None),
+        };
+        let Some(entry) = pattern_index
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| groups.get_mut(i))
+        else {
+            continue;
+        };
+        match group {
+            None => entry.wrapper = index,
+            Some("match") => entry.match_group = Some(index),
+            Some(group) => entry.named.push((group.to_string(), index)),
+        }
+    }
+    groups
+}
+
+/// Values of the named groups that took part in a match.
+fn named_groups(
+    caps: &regex::Captures<'_>,
+    groups: Option<&PatternGroups>,
+) -> BTreeMap<String, String> {
+    groups
+        .map(|groups| {
+            groups
+                .named
+                .iter()
+                .filter_map(|(name, index)| {
+                    caps.get(*index)
+                        .map(|m| (name.clone(), m.as_str().to_string()))
+                })
+                .collect()
         })
-        .collect()
+        .unwrap_or_default()
 }
 
+/// The index of the pattern that matched, the captured text (the `match`
+/// group if the pattern has one) and its offset within the whole match.
 fn captured_text_and_offset(
     caps: &regex::Captures<'_>,
-    match_group_indices: &[usize],
-) -> (String, Option<(usize, usize)>) {
-    for &idx in match_group_indices {
-        if let Some(m) = caps.get(idx) {
-            let whole = caps.get(0).unwrap();
-            let relative_start = m.start() - whole.start();
-            let length = m.as_str().len();
-            return (m.as_str().to_string(), Some((relative_start, length)));
-        }
+    pattern_groups: &[PatternGroups],
+) -> (usize, String, Option<(usize, usize)>) {
+    let Some(index) = pattern_groups
+        .iter()
+        .position(|groups| caps.get(groups.wrapper).is_some())
+    else {
+        return (0, caps[0].to_string(), None);
+    };
+
+    if let Some(m) = pattern_groups[index]
+        .match_group
+        .and_then(|group| caps.get(group))
+    {
+        let whole = caps.get(0).unwrap();
+        let relative_start = m.start() - whole.start();
+        let length = m.as_str().len();
+        return (
+            index,
+            m.as_str().to_string(),
+            Some((relative_start, length)),
+        );
     }
 
-    (caps[0].to_string(), None)
+    (index, caps[0].to_string(), None)
 }
 
 fn count_matches(lines: &[String], pattern: &Regex) -> usize {
@@ -272,19 +513,18 @@ fn count_matches(lines: &[String], pattern: &Regex) -> usize {
         .sum()
 }
 
-fn count_unique_matches(
-    lines: &[String],
-    pattern: &Regex,
-    match_group_indices: &[usize],
-) -> usize {
-    let mut seen = std::collections::HashSet::new();
-    for line in lines {
-        for caps in pattern.captures_iter(line) {
-            let (text, _) = captured_text_and_offset(&caps, match_group_indices);
-            seen.insert(text);
-        }
+/// Display width of a line once tabs are expanded.
+fn display_width(line: &str) -> usize {
+    let expanded = expand_tabs(line, &tab_positions_for(line));
+    UnicodeWidthStr::width(expanded.as_str())
+}
+
+fn rows_for_width(display_width: usize, width: usize) -> usize {
+    if width == 0 {
+        1
+    } else {
+        display_width.div_ceil(width).max(1)
     }
-    seen.len()
 }
 
 fn tab_positions_for(line: &str) -> Vec<usize> {
@@ -325,7 +565,7 @@ fn expand_tabs(line: &str, tab_positions: &[usize]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config;
+    use crate::{ansi, config};
 
     fn make_hinter(input: &[&str], width: usize, reuse_hints: bool) -> Hinter {
         let lines: Vec<String> = input.iter().map(|s| s.to_string()).collect();
@@ -370,7 +610,7 @@ mod tests {
         ];
 
         let mut hinter = make_hinter(&input, 100, true);
-        let result = hinter.run("", &[], 100);
+        let result = hinter.run("", &[], 100, 0..input.len());
         assert!(!result.is_empty());
     }
 
@@ -383,7 +623,7 @@ mod tests {
         ];
 
         let mut hinter = make_hinter(&input, 100, true);
-        let _ = hinter.run("", &[], 100);
+        let _ = hinter.run("", &[], 100, 0..input.len());
 
         // With reuse_hints=true, the same text should get the same hint
         // so we should only have generated hints for unique matches
@@ -399,8 +639,251 @@ mod tests {
         ];
 
         let mut hinter = make_hinter(&input, 100, false);
-        let _ = hinter.run("", &[], 100);
+        let _ = hinter.run("", &[], 100, 0..input.len());
         // Running twice should work without panicking
-        let _ = hinter.run("", &[], 100);
+        let _ = hinter.run("", &[], 100, 0..input.len());
+    }
+
+    #[test]
+    fn visible_matches_get_the_shortest_hints() {
+        let input = vec![
+            "10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4", "10.0.0.5", "10.0.0.6",
+        ];
+
+        let mut hinter = make_hinter(&input, 100, true);
+        let _ = hinter.run("", &[], 100, 0..1);
+
+        let visible = target_for(&hinter, "10.0.0.1");
+        assert_eq!(visible.hint.len(), 1);
+        assert!(hinter
+            .target_by_text
+            .values()
+            .all(|t| t.hint.len() >= visible.hint.len()));
+    }
+
+    #[test]
+    fn rerendering_keeps_hints_stable() {
+        let input = vec!["10.0.0.1", "10.0.0.2", "10.0.0.3"];
+
+        let mut hinter = make_hinter(&input, 100, true);
+        let _ = hinter.run("", &[], 100, 0..input.len());
+        let first = target_for(&hinter, "10.0.0.2").hint.clone();
+        let _ = hinter.run("", &[], 100, 0..input.len());
+
+        assert_eq!(target_for(&hinter, "10.0.0.2").hint, first);
+    }
+
+    #[test]
+    fn regions_share_one_hint_space() {
+        let input = vec!["10.0.0.1", "", "10.0.0.2"];
+
+        let mut hinter = make_hinter(&input, 20, true);
+        let regions = hinter.run_regions("", &[], &[(0..1, 10), (2..3, 10)]);
+        assert_eq!(regions.len(), 2);
+
+        let first = target_for(&hinter, "10.0.0.1");
+        let second = target_for(&hinter, "10.0.0.2");
+        assert_ne!(first.hint, second.hint);
+        assert_eq!(first.line, 0);
+        assert_eq!(second.line, 2);
+    }
+
+    #[test]
+    fn equal_texts_in_different_panes_are_separate_targets() {
+        let input = vec!["10.0.0.1", "10.0.0.1", "10.0.0.1"];
+
+        let mut hinter = make_hinter(&input, 20, true).with_pane_starts(&[0, 2]);
+        assert_eq!(hinter.match_count(), 2);
+        let _ = hinter.run_regions("", &[], &[(0..2, 10), (2..3, 10)]);
+
+        let first = &hinter.target_by_text[&(0, "10.0.0.1".to_string())];
+        let second = &hinter.target_by_text[&(1, "10.0.0.1".to_string())];
+        assert_ne!(first.hint, second.hint);
+        assert_eq!(first.line, 1);
+        assert_eq!(second.line, 2);
+    }
+
+    #[test]
+    fn matches_span_soft_wrapped_rows() {
+        let input = vec!["see https://example.com/a/long/path"];
+
+        let mut hinter = make_hinter(&input, 10, true);
+        assert_eq!(hinter.row_count(0, 10), 4);
+
+        let result = hinter.run("", &[], 10, 0..input.len());
+        let rows = ansi::split_rows(&result[0].content, 10);
+        assert_eq!(rows.len(), 4);
+        assert!(hinter
+            .target_by_text
+            .contains_key(&(0, "https://example.com/a/long/path".to_string())));
+    }
+
+    #[test]
+    fn keeps_hints_for_matches_still_on_screen() {
+        let before = vec!["10.0.0.1", "10.0.0.2"];
+        let mut previous = make_hinter(&before, 100, true);
+        let _ = previous.run("", &[], 100, 0..before.len());
+
+        let after = vec!["10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4"];
+        let mut hinter = make_hinter(&after, 100, true);
+        assert!(hinter.keep_hints_from(&previous));
+        let _ = hinter.run("", &[], 100, 0..after.len());
+
+        for text in ["10.0.0.1", "10.0.0.2"] {
+            assert_eq!(
+                target_for(&hinter, text).hint,
+                target_for(&previous, text).hint
+            );
+        }
+        let hints: Vec<&String> = hinter.target_by_text.values().map(|t| &t.hint).collect();
+        for a in &hints {
+            for b in &hints {
+                assert!(a == b || !a.starts_with(b.as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn cannot_keep_hints_that_leave_no_room() {
+        let before = vec!["10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4"];
+        let mut previous = make_hinter(&before, 100, true);
+        let _ = previous.run("", &[], 100, 0..before.len());
+
+        let after = vec!["10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4", "10.0.0.5"];
+        let mut hinter = make_hinter(&after, 100, true);
+        assert!(!hinter.keep_hints_from(&previous));
+    }
+
+    fn target_for<'a>(hinter: &'a Hinter, text: &str) -> &'a Target {
+        hinter
+            .target_by_text
+            .get(&(0, text.to_string()))
+            .unwrap_or_else(|| panic!("no target for {text}"))
+    }
+
+    #[test]
+    fn specific_builtins_win_over_sha_and_digit() {
+        let lines = vec![
+            "id d6f4b4ac-4b78-4d79-96a1-eb9ab72f2c59".to_string(),
+            "commit 1234567abcdef0".to_string(),
+        ];
+        let mut hinter = Hinter::new(&lines, 100, &Config::default());
+        let _ = hinter.run("", &[], 100, 0..lines.len());
+
+        let uuid = target_for(&hinter, "d6f4b4ac-4b78-4d79-96a1-eb9ab72f2c59");
+        assert_eq!(uuid.pattern, "uuid");
+        assert_eq!(target_for(&hinter, "1234567abcdef0").pattern, "sha");
+        assert!(!hinter
+            .target_by_text
+            .contains_key(&(0, "d6f4b4ac".to_string())));
+        assert!(!hinter
+            .target_by_text
+            .contains_key(&(0, "1234567".to_string())));
+    }
+
+    #[test]
+    fn targets_record_pattern_line_and_columns() {
+        let input = vec![
+            "see https://example.com/x for details",
+            "commit 1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b",
+            "  from 192.168.0.1",
+        ];
+        let mut hinter = make_hinter(&input, 100, true);
+        let _ = hinter.run("", &[], 100, 0..input.len());
+
+        let url = target_for(&hinter, "https://example.com/x");
+        assert_eq!(url.pattern, "url");
+        assert_eq!((url.line, url.columns.clone()), (0, 4..25));
+
+        let sha = target_for(&hinter, "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b");
+        assert_eq!(sha.pattern, "sha");
+        assert_eq!((sha.line, sha.columns.start), (1, 7));
+
+        let ip = target_for(&hinter, "192.168.0.1");
+        assert_eq!(ip.pattern, "ip");
+        assert_eq!((ip.line, ip.columns.clone()), (2, 7..18));
+    }
+
+    #[test]
+    fn target_columns_cover_the_match_group_only() {
+        let input = vec!["        modified:   src/main.rs"];
+        let mut hinter = make_hinter(&input, 100, true);
+        let _ = hinter.run("", &[], 100, 0..input.len());
+
+        let target = target_for(&hinter, "src/main.rs");
+        assert_eq!(target.pattern, "git-status");
+        assert_eq!(target.columns, 20..31);
+    }
+
+    #[test]
+    fn target_columns_count_display_width() {
+        let input = vec!["日本 10.0.0.1"];
+        let mut hinter = make_hinter(&input, 100, true);
+        let _ = hinter.run("", &[], 100, 0..input.len());
+
+        assert_eq!(target_for(&hinter, "10.0.0.1").columns, 5..13);
+    }
+
+    #[test]
+    fn rows_below_counts_wrapped_rows() {
+        let input = vec!["abcdefghij", "x", "0123456789ab"];
+        let hinter = make_hinter(&input, 4, true);
+
+        // "abcdefghij" takes 3 rows, "x" 1 and the last line 3
+        assert_eq!(hinter.rows_below(0, 0, 3, 4), 6);
+        assert_eq!(hinter.rows_below(0, 9, 3, 4), 4);
+        assert_eq!(hinter.rows_below(2, 11, 3, 4), 0);
+        assert_eq!(hinter.rows_below(0, 0, 1, 4), 2);
+    }
+
+    #[test]
+    fn rows_above_counts_wrapped_rows() {
+        let input = vec!["abcdefghij", "x", "0123456789ab"];
+        let hinter = make_hinter(&input, 4, true);
+
+        assert_eq!(hinter.rows_above(0, 0, 0, 4), 0);
+        assert_eq!(hinter.rows_above(0, 9, 0, 4), 2);
+        assert_eq!(hinter.rows_above(2, 5, 0, 4), 5);
+        assert_eq!(hinter.rows_above(2, 0, 1, 4), 1);
+    }
+
+    #[test]
+    fn targets_record_named_groups_of_their_pattern() {
+        let lines = vec!["at src/lib.rs:12 and 0xff".to_string()];
+        let patterns = vec![
+            Pattern {
+                name: "loc".to_string(),
+                regex: r"(?P<file>\S+\.rs):(?P<lnum>\d+)".to_string(),
+            },
+            Pattern {
+                name: "hex".to_string(),
+                regex: r"0x(?P<match>(?P<file>[0-9a-f]+))".to_string(),
+            },
+        ];
+        let alphabet: Vec<String> = "asdf".chars().map(|c| c.to_string()).collect();
+        let mut hinter = Hinter::with_options(
+            &lines,
+            100,
+            &patterns,
+            &alphabet,
+            "left".to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            true,
+        );
+        let _ = hinter.run("", &[], 100, 0..1);
+
+        let loc = target_for(&hinter, "src/lib.rs:12");
+        assert_eq!(loc.pattern, "loc");
+        assert_eq!(loc.groups["file"], "src/lib.rs");
+        assert_eq!(loc.groups["lnum"], "12");
+
+        let hex = target_for(&hinter, "ff");
+        assert_eq!(hex.pattern, "hex");
+        assert_eq!(hex.groups.len(), 1);
+        assert_eq!(hex.groups["file"], "ff");
     }
 }
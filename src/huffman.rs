@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
 use crate::priority_queue::PriorityQueue;
 
 struct HuffmanNode {
@@ -41,6 +43,61 @@ pub fn generate_hints(alphabet: &[String], n: usize) -> Vec<String> {
     result
 }
 
+/// Generates `n` hints that are prefix-free with respect to the `taken`
+/// hints, so new matches can be hinted without changing the hints already
+/// on screen. Returns `None` when the taken hints leave no room.
+///
+/// The free branches of the code tree the taken hints span are hints
+/// already; the shortest one is split until there are enough of them.
+pub fn generate_hints_avoiding(
+    alphabet: &[String],
+    n: usize,
+    taken: &[String],
+) -> Option<Vec<String>> {
+    if taken.is_empty() {
+        return Some(generate_hints(alphabet, n));
+    }
+    if n == 0 {
+        return Some(Vec::new());
+    }
+
+    // Inner nodes of the tree are the proper prefixes of the taken hints
+    let mut inner = BTreeSet::new();
+    for hint in taken {
+        for (end, _) in hint.char_indices() {
+            inner.insert(&hint[..end]);
+        }
+    }
+
+    let mut free: BTreeMap<usize, VecDeque<String>> = BTreeMap::new();
+    for node in &inner {
+        for symbol in alphabet {
+            let branch = format!("{node}{symbol}");
+            if !inner.contains(branch.as_str()) && !taken.contains(&branch) {
+                free.entry(branch.chars().count())
+                    .or_default()
+                    .push_back(branch);
+            }
+        }
+    }
+
+    let mut count: usize = free.values().map(VecDeque::len).sum();
+    while count < n && alphabet.len() > 1 {
+        let mut shortest = free.first_entry()?;
+        let branch = shortest.get_mut().pop_front()?;
+        if shortest.get().is_empty() {
+            shortest.remove();
+        }
+        let length = branch.chars().count() + 1;
+        let children = free.entry(length).or_default();
+        children.extend(alphabet.iter().map(|symbol| format!("{branch}{symbol}")));
+        count += alphabet.len() - 1;
+    }
+
+    let hints: Vec<String> = free.into_values().flatten().take(n).collect();
+    (hints.len() == n).then_some(hints)
+}
+
 fn initial_number_of_branches(n: usize, arity: usize) -> usize {
     let mut result = arity;
     let n = n as i64;
@@ -160,4 +217,51 @@ mod tests {
             .collect();
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn avoiding_returns_prefix_free_hints() {
+        let taken: Vec<String> = vec!["s".to_string(), "aa".to_string()];
+        let result = generate_hints_avoiding(&alphabet_a(), 4, &taken).unwrap();
+
+        assert_eq!(result.len(), 4);
+        for hint in &result {
+            for t in &taken {
+                assert!(!hint.starts_with(t.as_str()) && !t.starts_with(hint.as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn avoiding_uses_the_free_branches_first() {
+        let taken: Vec<String> = vec!["a", "s", "d", "fa"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let result = generate_hints_avoiding(&alphabet_a(), 3, &taken).unwrap();
+        assert_eq!(result, vec!["fs", "fd", "ff"]);
+
+        // Splitting the shortest free branch makes room for more
+        let result = generate_hints_avoiding(&alphabet_a(), 5, &taken).unwrap();
+        assert_eq!(result, vec!["fd", "ff", "fsa", "fss", "fsd"]);
+    }
+
+    #[test]
+    fn avoiding_handles_many_matches() {
+        let taken = generate_hints(&alphabet_a(), 50)[..40].to_vec();
+        let result = generate_hints_avoiding(&alphabet_a(), 2000, &taken).unwrap();
+
+        assert_eq!(result.len(), 2000);
+        let all: Vec<&String> = taken.iter().chain(&result).collect();
+        let distinct: std::collections::HashSet<&&String> = all.iter().collect();
+        assert_eq!(distinct.len(), all.len());
+        for hint in &result {
+            assert!(taken.iter().all(|t| !hint.starts_with(t.as_str())));
+        }
+    }
+
+    #[test]
+    fn avoiding_fails_when_taken_hints_are_complete() {
+        let taken: Vec<String> = alphabet_a();
+        assert!(generate_hints_avoiding(&alphabet_a(), 1, &taken).is_none());
+    }
 }
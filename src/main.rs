@@ -4,46 +4,100 @@ mod config;
 mod hinter;
 mod huffman;
 mod match_formatter;
+mod multi_select;
 mod pane_capture;
 mod priority_queue;
 mod renderer;
 mod state;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use zellij_tile::prelude::*;
-use crate::config::Config;
+use crate::action::{ActionOutcome, Item, Selection};
+use crate::config::{ActionKey, Config};
 use crate::hinter::Hinter;
+use crate::multi_select::{MultiSelection, Pick};
+use crate::pane_capture::{CapturedPane, FrameInsets, PaneGeometry, TargetPane};
 use crate::state::PluginPhase;
 
+/// How long to wait for an action's result before closing anyway.
+const ACTION_WAIT_SECS: f64 = 1.0;
+/// How long a confirmation stays on screen.
+const NOTIFICATION_SECS: f64 = 1.0;
+/// Time for an OSC 52 sequence to be rendered before the plugin closes.
+const OUTPUT_SECS: f64 = 0.2;
+
 struct ZellijFingers {
     phase: PluginPhase,
+    configuration: BTreeMap<String, String>,
     config: Config,
     hinter: Option<Hinter>,
     input: String,
     multi_mode: bool,
-    selected_hints: Vec<String>,
-    multi_matches: Vec<String>,
-    pane_content: Vec<String>,
-    pane_rows: usize,
-    pane_cols: usize,
+    multi_selection: MultiSelection,
+    capture_targets: Vec<(u32, PaneGeometry)>,
+    panes: Vec<CapturedPane>,
+    pending_panes: Vec<CapturedPane>,
+    overlay_origin: (usize, usize),
+    viewport_rows: usize,
+    viewport_cols: usize,
+    scroll_offset: usize,
     target_pane_id: Option<u32>,
+    pane_manifest: Option<PaneManifest>,
+    active_tab: Option<usize>,
+    session_name: Option<String>,
+    capture_in_flight: bool,
+    pane_cwds: HashMap<u32, PathBuf>,
+    cwds_requested: bool,
+    /// Index and action of the pipeline step whose command is running.
+    running_step: Option<(usize, String)>,
+    /// Index the next pipeline step gets.
+    next_step: usize,
+    /// Remaining steps of the action pipeline and the selection they run on.
+    pending_steps: VecDeque<String>,
+    selection: Option<Selection>,
+    action_errors: Vec<String>,
+    action_notice: Option<String>,
+    clipboard_output: Option<String>,
+    watchdog_deadline: Option<Instant>,
+    refresh_deadline: Option<Instant>,
 }
 
 impl Default for ZellijFingers {
     fn default() -> Self {
         Self {
             phase: PluginPhase::WaitingForPermissions,
+            configuration: BTreeMap::new(),
             config: Config::default(),
             hinter: None,
             input: String::new(),
             multi_mode: false,
-            selected_hints: Vec::new(),
-            multi_matches: Vec::new(),
-            pane_content: Vec::new(),
-            pane_rows: 0,
-            pane_cols: 0,
+            multi_selection: MultiSelection::default(),
+            capture_targets: Vec::new(),
+            panes: Vec::new(),
+            pending_panes: Vec::new(),
+            overlay_origin: (0, 0),
+            viewport_rows: 0,
+            viewport_cols: 0,
+            scroll_offset: 0,
             target_pane_id: None,
+            pane_manifest: None,
+            active_tab: None,
+            session_name: None,
+            capture_in_flight: false,
+            pane_cwds: HashMap::new(),
+            cwds_requested: false,
+            running_step: None,
+            next_step: 0,
+            pending_steps: VecDeque::new(),
+            selection: None,
+            action_errors: Vec::new(),
+            action_notice: None,
+            clipboard_output: None,
+            watchdog_deadline: None,
+            refresh_deadline: None,
         }
     }
 }
@@ -53,95 +107,216 @@ register_plugin!(ZellijFingers);
 impl ZellijPlugin for ZellijFingers {
     fn load(&mut self, configuration: BTreeMap<String, String>) {
         self.config = Config::from_kdl(&configuration);
+        self.configuration = configuration;
 
         request_permission(&[
             PermissionType::RunCommands,
             PermissionType::ReadApplicationState,
             PermissionType::ChangeApplicationState,
+            PermissionType::WriteToStdin,
+            PermissionType::OpenFiles,
+            PermissionType::OpenTerminalsOrPlugins,
+            PermissionType::InterceptInput,
         ]);
 
         subscribe(&[
             EventType::Key,
+            EventType::InterceptedKeyPress,
+            EventType::ModeUpdate,
+            EventType::TabUpdate,
             EventType::PaneUpdate,
             EventType::RunCommandResult,
             EventType::PermissionRequestResult,
+            EventType::Timer,
         ]);
+        // No timeout until permissions are granted: Zellij's prompt waits
+        // for the user for as long as it takes
     }
 
     fn update(&mut self, event: Event) -> bool {
-        match &self.phase {
-            PluginPhase::WaitingForPermissions => {
-                if let Event::PermissionRequestResult(PermissionStatus::Granted) = event {
-                    // Resize floating pane to fill the entire screen
-                    let plugin_ids = get_plugin_ids();
-                    let pane_id = PaneId::Plugin(plugin_ids.plugin_id);
-                    let coords = FloatingPaneCoordinates::new(
-                        Some("0".to_string()),
-                        Some("0".to_string()),
-                        Some("100%".to_string()),
-                        Some("100%".to_string()),
-                        None,
-                    ).unwrap();
-                    change_floating_panes_coordinates(vec![(pane_id, coords)]);
+        // Capturing moves focus to the target pane, so keys are intercepted
+        // for the whole session and handled the same as our own
+        let event = match event {
+            Event::InterceptedKeyPress(key) => Event::Key(key),
+            event => event,
+        };
+        if let Event::Key(ref key) = event {
+            if key.bare_key == BareKey::Esc {
+                self.close();
+                return false;
+            }
+        }
+        if let Event::Timer(_) = event {
+            if deadline_passed(self.watchdog_deadline) {
+                match self.phase {
+                    // A step that outlasts `timeout` is left running. Its
+                    // failure is still reported if it arrives in time.
+                    PluginPhase::RunningAction if !self.pending_steps.is_empty() => {
+                        if let Some((_, step)) = self.running_step.take() {
+                            if self.config.stop_on_failure {
+                                self.action_errors
+                                    .push(format!("Action `{step}` timed out"));
+                                self.pending_steps.clear();
+                            }
+                        }
+                        self.run_next_step();
+                    }
+                    // Actions that are still running are left to finish
+                    PluginPhase::RunningAction | PluginPhase::Notifying(_) => self.close(),
+                    _ => self.fail(self.timeout_message()),
+                }
+                return true;
+            }
+        }
+        if let Event::RunCommandResult(exit_code, ref stdout, _, ref context) = event {
+            if self.handle_cwd_result(exit_code, stdout, context) {
+                return false;
+            }
+        }
 
+        match &self.phase {
+            PluginPhase::WaitingForPermissions => match event {
+                Event::PermissionRequestResult(PermissionStatus::Granted) => {
+                    intercept_key_presses();
                     self.phase = PluginPhase::Capturing;
-                    pane_capture::request_pane_capture();
+                    self.arm_watchdog(self.config.timeout);
+                    true
                 }
-                false
-            }
+                Event::PermissionRequestResult(PermissionStatus::Denied) => {
+                    self.fail(
+                        "Permission denied: zellij-fingers needs to run commands, read and \
+                         change the application state and intercept key presses"
+                            .to_string(),
+                    );
+                    true
+                }
+                _ => false,
+            },
             PluginPhase::Capturing => {
                 match event {
+                    Event::ModeUpdate(mode_info) => {
+                        if self.session_name.is_none() {
+                            self.session_name = mode_info.session_name;
+                        }
+                        self.try_request_capture();
+                        false
+                    }
+                    Event::TabUpdate(tabs) => {
+                        self.active_tab = pane_capture::active_tab_position(&tabs);
+                        if !self.resolve_capture_targets() {
+                            return true;
+                        }
+                        self.try_request_capture();
+                        false
+                    }
                     Event::PaneUpdate(pane_manifest) => {
-                        if self.target_pane_id.is_none() {
-                            self.target_pane_id =
-                                pane_capture::find_target_pane(&pane_manifest);
-                            if let Some((rows, cols)) =
-                                pane_capture::get_pane_dimensions(&pane_manifest, self.target_pane_id)
-                            {
-                                self.pane_rows = rows;
-                                self.pane_cols = cols;
-                            }
+                        self.pane_manifest = Some(pane_manifest);
+                        if !self.resolve_capture_targets() {
+                            return true;
                         }
-                        // If we already have content, try to start hinting
-                        self.try_start_hinting()
+                        self.try_request_capture();
+                        false
                     }
-                    Event::RunCommandResult(exit_code, stdout, _stderr, _context) => {
-                        if exit_code == Some(0) {
-                            let content = String::from_utf8_lossy(&stdout).to_string();
-                            self.pane_content = content
-                                .lines()
-                                .map(|l| l.trim_end().to_string())
-                                .collect();
+                    Event::RunCommandResult(exit_code, stdout, stderr, context) => {
+                        match self.handle_capture_result(exit_code, &stdout, &stderr, &context) {
+                            Ok(true) => {
+                                self.panes = std::mem::take(&mut self.pending_panes);
+                                self.try_start_hinting()
+                            }
+                            Ok(false) => false,
+                            Err(message) => {
+                                self.fail(message);
+                                true
+                            }
                         }
-                        // If we already have pane dimensions, try to start hinting
-                        self.try_start_hinting()
                     }
                     _ => false,
                 }
             }
-            PluginPhase::Hinting => {
-                if let Event::Key(key) = event {
+            PluginPhase::Hinting => match event {
+                Event::Key(key) => {
                     self.handle_key(key);
                     true
-                } else {
+                }
+                Event::Timer(_) => {
+                    if deadline_passed(self.refresh_deadline) {
+                        self.refresh_capture();
+                    }
                     false
                 }
-            }
-            PluginPhase::Done => false,
+                Event::RunCommandResult(exit_code, stdout, stderr, context) => {
+                    match self.handle_capture_result(exit_code, &stdout, &stderr, &context) {
+                        Ok(true) => {
+                            let refreshed = self.apply_refresh();
+                            self.schedule_refresh();
+                            refreshed
+                        }
+                        Ok(false) => false,
+                        // A failed refresh keeps the current hints on screen
+                        Err(_) => {
+                            self.pending_panes.clear();
+                            self.schedule_refresh();
+                            false
+                        }
+                    }
+                }
+                _ => false,
+            },
+            PluginPhase::RunningAction => match event {
+                Event::RunCommandResult(exit_code, _stdout, stderr, context) => {
+                    self.handle_action_result(exit_code, &stderr, &context)
+                }
+                _ => false,
+            },
+            PluginPhase::Notifying(_) | PluginPhase::Error(_) | PluginPhase::Done => false,
+        }
+    }
+
+    /// Pipe arguments override the plugin configuration, e.g.
+    /// `zellij pipe --plugin ... --args target_pane=left`. They only take
+    /// effect before the target pane has been picked. The CLI pipe is
+    /// unblocked once its arguments are consumed, so the shell that sent it
+    /// does not wait for a selection.
+    fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
+        if self.target_pane_id.is_none() && !pipe_message.args.is_empty() {
+            self.configuration.extend(pipe_message.args);
+            self.config = Config::from_kdl(&self.configuration);
+        }
+        // Host test builds keep the exported `pipe` alive in place of libc's,
+        // and plugin commands cannot link outside of Zellij
+        #[cfg(not(test))]
+        if let PipeSource::Cli(_) = pipe_message.source {
+            unblock_cli_pipe_input(&pipe_message.name);
         }
+        false
     }
 
     fn render(&mut self, rows: usize, cols: usize) {
         match self.phase {
-            PluginPhase::Hinting => {
+            // The overlay stays up while waiting for the action's result
+            PluginPhase::Hinting | PluginPhase::RunningAction => {
+                self.viewport_rows = rows;
+                self.viewport_cols = cols;
+                let selected = self.multi_selection.keys();
                 if let Some(ref mut hinter) = self.hinter {
-                    let output = renderer::render(
-                        hinter,
-                        &self.input,
-                        &self.selected_hints,
-                        rows,
-                        cols,
-                    );
+                    let output = if self.config.all_panes {
+                        renderer::render_panes(
+                            hinter,
+                            &self.input,
+                            &selected,
+                            &self.panes,
+                            self.overlay_origin,
+                        )
+                    } else {
+                        renderer::render(
+                            hinter,
+                            &self.input,
+                            &selected,
+                            rows,
+                            cols,
+                            self.scroll_offset,
+                        )
+                    };
                     print!("{}", output);
                 }
             }
@@ -150,83 +325,684 @@ impl ZellijPlugin for ZellijFingers {
             }
             PluginPhase::Capturing => {
                 println!(
-                    "Capturing pane content... (target_pane_id: {:?}, content_lines: {})",
+                    "Capturing pane content... (target_pane_id: {:?}, captured: {}/{})",
                     self.target_pane_id,
-                    self.pane_content.len()
+                    self.pending_panes.len(),
+                    self.capture_targets.len()
                 );
             }
+            PluginPhase::Notifying(ref message) => {
+                if let Some(sequence) = self.clipboard_output.take() {
+                    print!("{}", sequence);
+                }
+                println!("{}", message);
+            }
+            PluginPhase::Error(ref message) => {
+                println!("zellij-fingers: {}", message);
+                println!();
+                println!("Press Esc to close");
+            }
             PluginPhase::Done => {}
         }
     }
 }
 
 impl ZellijFingers {
-    /// Try to transition to Hinting once we have both pane content and dimensions.
-    fn try_start_hinting(&mut self) -> bool {
-        if !self.pane_content.is_empty() && self.pane_cols > 0 {
-            let hinter = Hinter::new(
-                &self.pane_content,
-                self.pane_cols,
-                &self.config,
+    /// Start the timeout for the current phase. A `Timer` event that arrives
+    /// after the deadline while still in that phase is reported as an error,
+    /// or closes the plugin once a selection has been made.
+    fn arm_watchdog(&mut self, secs: f64) {
+        self.watchdog_deadline = Some(Instant::now() + Duration::from_secs_f64(secs));
+        set_timeout(secs);
+    }
+
+    fn close(&mut self) {
+        clear_key_presses_intercepts();
+        close_self();
+        self.phase = PluginPhase::Done;
+    }
+
+    /// Switch to the error screen, taking focus back from the target pane so
+    /// the message is visible.
+    fn fail(&mut self, message: String) {
+        if self.phase == PluginPhase::Capturing {
+            focus_plugin_pane(get_plugin_ids().plugin_id, true);
+        }
+        self.watchdog_deadline = None;
+        self.capture_in_flight = false;
+        self.phase = PluginPhase::Error(message);
+    }
+
+    fn timeout_message(&self) -> String {
+        if self.target_pane_id.is_none() {
+            format!(
+                "Timed out looking for the target pane `{}`",
+                self.config.target_pane
+            )
+        } else {
+            format!(
+                "Timed out capturing pane content ({}/{} panes captured)",
+                self.pending_panes.len(),
+                self.capture_targets.len()
+            )
+        }
+    }
+
+    /// Pick the target pane and, in `all_panes` mode, every other pane in its
+    /// tab. Needs both the pane manifest and the active tab. Returns `false`
+    /// and shows the error screen if `target_pane` is invalid.
+    fn resolve_capture_targets(&mut self) -> bool {
+        if self.target_pane_id.is_some() {
+            return true;
+        }
+        let (Some(manifest), Some(active_tab)) = (&self.pane_manifest, self.active_tab) else {
+            return true;
+        };
+        let target = match TargetPane::parse(&self.config.target_pane) {
+            Ok(target) => target,
+            Err(message) => {
+                self.fail(message);
+                return false;
+            }
+        };
+        let Some(target_id) = pane_capture::find_target_pane(manifest, active_tab, &target) else {
+            return true;
+        };
+
+        self.capture_targets = if self.config.all_panes {
+            pane_capture::find_tab_panes(manifest, target_id)
+        } else {
+            pane_capture::get_pane_geometry(manifest, target_id, false)
+                .map(|geometry| vec![(target_id, geometry)])
+                .unwrap_or_default()
+        };
+
+        // Cover the whole tab in `all_panes` mode, otherwise just the target
+        // pane so the highlights sit over the text they belong to
+        let insets =
+            FrameInsets::of_plugin(manifest, get_plugin_ids().plugin_id).unwrap_or_default();
+        let (viewport_x, viewport_y) = pane_capture::viewport_origin(manifest, active_tab);
+        if self.config.all_panes {
+            self.overlay_origin = (viewport_x + insets.left, viewport_y + insets.top);
+            move_overlay("0", "0", "100%", "100%");
+        } else if let Some((_, geometry)) = self.capture_targets.first() {
+            let outer = insets.around(geometry);
+            move_overlay(
+                &outer.x.saturating_sub(viewport_x).to_string(),
+                &outer.y.saturating_sub(viewport_y).to_string(),
+                &outer.cols.to_string(),
+                &outer.rows.to_string(),
             );
-            self.hinter = Some(hinter);
-            self.phase = PluginPhase::Hinting;
-            true
+        }
+        self.target_pane_id = Some(target_id);
+        true
+    }
+
+    /// Dump the next pane to capture once the session name is known.
+    /// Panes are captured one at a time since each dump focuses its pane.
+    fn try_request_capture(&mut self) {
+        let Some(ref session_name) = self.session_name else {
+            return;
+        };
+        if !self.cwds_requested && !self.capture_targets.is_empty() {
+            for (pane_id, _) in &self.capture_targets {
+                pane_capture::request_pane_cwd(session_name, *pane_id);
+            }
+            self.cwds_requested = true;
+        }
+        if self.capture_in_flight {
+            return;
+        }
+        if let Some((pane_id, _)) = self.capture_targets.get(self.pending_panes.len()) {
+            // Only the single-pane overlay can scroll, so only it needs the scrollback
+            pane_capture::request_pane_capture(session_name, *pane_id, !self.config.all_panes);
+            self.capture_in_flight = true;
+        }
+    }
+
+    /// Run the action pipeline for `action_key` on the selection.
+    fn run_action(&mut self, action_key: ActionKey, selection: Selection) {
+        let steps = self
+            .config
+            .action_for(action_key, selection.pattern.as_deref());
+        self.pending_steps = steps.iter().cloned().collect();
+        self.selection = Some(selection);
+        self.run_next_step();
+    }
+
+    /// Run pipeline steps until one has to wait for its command's result,
+    /// or finish once none are left.
+    fn run_next_step(&mut self) {
+        while let Some(step) = self.pending_steps.pop_front() {
+            let Some(ref selection) = self.selection else {
+                break;
+            };
+            let index = self.next_step;
+            self.next_step += 1;
+            let target_pane_id = self.target_pane_id;
+            match action::execute_action(&self.config, &step, index, selection, target_pane_id) {
+                ActionOutcome::Finished => {}
+                ActionOutcome::Running => {
+                    // Later steps wait for this one; the last is only
+                    // waited for briefly before closing
+                    let wait = if self.pending_steps.is_empty() {
+                        ACTION_WAIT_SECS
+                    } else {
+                        self.config.timeout
+                    };
+                    self.running_step = Some((index, step));
+                    self.phase = PluginPhase::RunningAction;
+                    self.arm_watchdog(wait);
+                    return;
+                }
+                ActionOutcome::Output(sequence) => {
+                    self.action_notice = Some(action::copied_notice(&selection.text));
+                    self.clipboard_output = Some(sequence);
+                }
+            }
+        }
+        self.finish_actions();
+    }
+
+    /// Once every action has finished, show the errors, show the
+    /// confirmation or pending clipboard output, or close.
+    fn finish_actions(&mut self) {
+        if self.running_step.is_some() {
+            return;
+        }
+
+        if !self.action_errors.is_empty() {
+            self.fail(self.action_errors.join("\n"));
+        } else if let (true, Some(notice)) = (
+            self.config.show_copied_notification,
+            self.action_notice.take(),
+        ) {
+            self.phase = PluginPhase::Notifying(notice);
+            self.arm_watchdog(NOTIFICATION_SECS);
+        } else if self.clipboard_output.is_some() {
+            self.phase = PluginPhase::Notifying(String::new());
+            self.arm_watchdog(OUTPUT_SECS);
         } else {
-            false
+            self.close();
+        }
+    }
+
+    /// Collect the result of the running step and carry on with the
+    /// pipeline. A late result of a step the watchdog moved on from only
+    /// records its failure.
+    fn handle_action_result(
+        &mut self,
+        exit_code: Option<i32>,
+        stderr: &[u8],
+        context: &BTreeMap<String, String>,
+    ) -> bool {
+        if context.get(pane_capture::CONTEXT_KIND).map(String::as_str) != Some(action::KIND_ACTION)
+        {
+            return false;
+        }
+        let step = action::result_step(context);
+        if step != self.running_step.as_ref().map(|(index, _)| *index) {
+            if let Err(message) = action::describe_result(exit_code, stderr, context) {
+                self.action_errors.push(message);
+            }
+            return false;
+        }
+        match action::describe_result(exit_code, stderr, context) {
+            Ok(notice) => self.action_notice = notice.or(self.action_notice.take()),
+            Err(message) => match action::osc52_fallback(context) {
+                Some((sequence, notice)) => {
+                    self.clipboard_output = Some(sequence);
+                    self.action_notice = Some(notice);
+                }
+                None => {
+                    self.action_errors.push(message);
+                    if self.config.stop_on_failure {
+                        self.pending_steps.clear();
+                    }
+                }
+            },
         }
+        self.running_step = None;
+        self.run_next_step();
+        true
+    }
+
+    /// Record a pane's working directory. Returns whether the result was a
+    /// cwd lookup at all.
+    fn handle_cwd_result(
+        &mut self,
+        exit_code: Option<i32>,
+        stdout: &[u8],
+        context: &BTreeMap<String, String>,
+    ) -> bool {
+        if context.get(pane_capture::CONTEXT_KIND).map(String::as_str)
+            != Some(pane_capture::KIND_CWD)
+        {
+            return false;
+        }
+        let pane_id = context
+            .get(pane_capture::CONTEXT_PANE_ID)
+            .and_then(|id| id.parse::<u32>().ok());
+        let cwd = String::from_utf8_lossy(stdout).trim().to_string();
+        if let (Some(id), Some(0), false) = (pane_id, exit_code, cwd.is_empty()) {
+            self.pane_cwds.insert(id, PathBuf::from(cwd));
+        }
+        true
+    }
+
+    /// Record the result of a pane capture and request the next one.
+    /// Returns `Ok(true)` once every target pane has been captured.
+    fn handle_capture_result(
+        &mut self,
+        exit_code: Option<i32>,
+        stdout: &[u8],
+        stderr: &[u8],
+        context: &BTreeMap<String, String>,
+    ) -> Result<bool, String> {
+        if context.get(pane_capture::CONTEXT_KIND).map(String::as_str)
+            != Some(pane_capture::KIND_CAPTURE)
+        {
+            return Ok(false);
+        }
+        self.capture_in_flight = false;
+
+        let pane_id = context
+            .get(pane_capture::CONTEXT_PANE_ID)
+            .and_then(|id| id.parse::<u32>().ok());
+        let Some(&(id, ref geometry)) = self
+            .capture_targets
+            .iter()
+            .find(|(id, _)| Some(*id) == pane_id)
+        else {
+            return Ok(false);
+        };
+
+        if let Some(ref session_name) = self.session_name {
+            pane_capture::cleanup_pane_capture(session_name, id);
+        }
+
+        if exit_code != Some(0) {
+            return Err(pane_capture::describe_capture_error(exit_code, stderr));
+        }
+        let lines = String::from_utf8_lossy(stdout)
+            .lines()
+            .map(|l| l.trim_end().to_string())
+            .collect();
+        self.pending_panes.push(CapturedPane {
+            id,
+            geometry: geometry.clone(),
+            lines,
+        });
+
+        if self.pending_panes.len() < self.capture_targets.len() {
+            self.try_request_capture();
+            return Ok(false);
+        }
+
+        // The capture focused the target pane; take focus back
+        focus_plugin_pane(get_plugin_ids().plugin_id, true);
+        Ok(true)
+    }
+
+    /// Transition to Hinting once every target pane has been captured, or to
+    /// the error screen if there is nothing to hint.
+    fn try_start_hinting(&mut self) -> bool {
+        if let Err(message) = self.config.validate_patterns() {
+            self.fail(message);
+            return true;
+        }
+        let has_content = self
+            .panes
+            .iter()
+            .any(|pane| pane.lines.iter().any(|line| !line.is_empty()));
+        if !has_content {
+            self.fail("The target pane is empty".to_string());
+            return true;
+        }
+
+        let hinter = self.build_hinter();
+        // In live mode matches may still show up later
+        if hinter.match_count() == 0 && self.config.live_refresh_interval.is_none() {
+            self.fail("No matches found".to_string());
+            return true;
+        }
+
+        self.hinter = Some(hinter);
+        self.watchdog_deadline = None;
+        self.phase = PluginPhase::Hinting;
+        self.schedule_refresh();
+        true
+    }
+
+    fn build_hinter(&self) -> Hinter {
+        let lines: Vec<String> = self
+            .panes
+            .iter()
+            .flat_map(|pane| pane.lines.iter().cloned())
+            .collect();
+        let width = self.panes[0].geometry.cols;
+        let pane_starts: Vec<usize> = self
+            .panes
+            .iter()
+            .scan(0, |start, pane| {
+                let first = *start;
+                *start += pane.lines.len();
+                Some(first)
+            })
+            .collect();
+        Hinter::new(&lines, width, &self.config).with_pane_starts(&pane_starts)
+    }
+
+    /// Arm the timer for the next live refresh, if live mode is enabled.
+    fn schedule_refresh(&mut self) {
+        if let Some(interval) = self.config.live_refresh_interval {
+            self.refresh_deadline = Some(Instant::now() + Duration::from_secs_f64(interval));
+            set_timeout(interval);
+        }
+    }
+
+    /// Re-capture the target panes in live mode. Refreshing is postponed
+    /// while a hint is partially typed so the prefix is never invalidated.
+    fn refresh_capture(&mut self) {
+        if self.capture_in_flight || !self.pending_panes.is_empty() {
+            return;
+        }
+        if !self.input.is_empty() {
+            self.schedule_refresh();
+            return;
+        }
+        self.try_request_capture();
+    }
+
+    /// Swap in freshly captured content, keeping the hints of matches that
+    /// are still on screen. Returns whether anything changed.
+    fn apply_refresh(&mut self) -> bool {
+        let refreshed = std::mem::take(&mut self.pending_panes);
+        let unchanged = refreshed.len() == self.panes.len()
+            && refreshed
+                .iter()
+                .zip(&self.panes)
+                .all(|(new, old)| new.lines == old.lines);
+        // The user started typing while the capture was running
+        if unchanged || !self.input.is_empty() {
+            return false;
+        }
+
+        let previous_panes = std::mem::replace(&mut self.panes, refreshed);
+        let mut hinter = self.build_hinter();
+        if let Some(ref previous) = self.hinter {
+            // Rather than moving hints the user may be reading, keep the
+            // previous capture until the new matches fit around them
+            if !hinter.keep_hints_from(previous) {
+                self.panes = previous_panes;
+                return false;
+            }
+        }
+        self.hinter = Some(hinter);
+        true
+    }
+
+    /// The pane a line of the combined hinter input was captured from, and
+    /// the line's index within that pane's capture.
+    fn pane_for_line(&self, line: usize) -> Option<(u32, usize)> {
+        let mut first_line = 0;
+        for pane in &self.panes {
+            if line < first_line + pane.lines.len() {
+                return Some((pane.id, line - first_line));
+            }
+            first_line += pane.lines.len();
+        }
+        None
+    }
+
+    /// How far to scroll a pane up from the bottom to bring the row holding
+    /// `column` of `line` to the top. Wrapped lines take several rows, so the
+    /// column picks the row within the line.
+    fn jump_offset(&self, hinter: &Hinter, line: usize, column: usize) -> Option<usize> {
+        let mut first_line = 0;
+        for pane in &self.panes {
+            let lines = first_line..first_line + pane.lines.len();
+            first_line = lines.end;
+            if !lines.contains(&line) {
+                continue;
+            }
+
+            let rows_below = hinter.rows_below(line, column, lines.end, pane.geometry.cols);
+            return Some((rows_below + 1).saturating_sub(pane.geometry.rows));
+        }
+        None
+    }
+
+    /// Where `column` of `line` is on screen, counting wrapped rows. With
+    /// all panes captured this orders matches across panes as they appear;
+    /// a single pane's scrollback rows continue above its top.
+    fn screen_position(&self, hinter: &Hinter, line: usize, column: usize) -> (usize, usize) {
+        let mut first_line = 0;
+        for pane in &self.panes {
+            let lines = first_line..first_line + pane.lines.len();
+            first_line = lines.end;
+            if !lines.contains(&line) {
+                continue;
+            }
+
+            let cols = pane.geometry.cols;
+            let row = hinter.rows_above(line, column, lines.start, cols);
+            let column_in_row = column.checked_rem(cols).unwrap_or(column);
+            return (pane.geometry.y + row, pane.geometry.x + column_in_row);
+        }
+        (line, column)
     }
 
     fn handle_key(&mut self, key: KeyWithModifier) {
-        match key.bare_key {
-            BareKey::Esc => {
-                close_self();
-                self.phase = PluginPhase::Done;
+        if let Some(rows) = scroll_rows(&key, self.viewport_rows) {
+            if rows < 0 {
+                self.scroll_down(rows.unsigned_abs());
+            } else {
+                self.scroll_up(rows.unsigned_abs());
             }
+            return;
+        }
+        match key.bare_key {
             BareKey::Enter if self.multi_mode => {
-                let result = self.multi_matches.join(" ");
-                if !result.is_empty() {
-                    action::execute_action(&self.config, &result);
-                }
-                close_self();
-                self.phase = PluginPhase::Done;
+                self.finish_multi_select();
             }
             BareKey::Tab => {
                 self.multi_mode = !self.multi_mode;
                 if !self.multi_mode {
-                    let result = self.multi_matches.join(" ");
-                    if !result.is_empty() {
-                        action::execute_action(&self.config, &result);
-                    }
-                    close_self();
-                    self.phase = PluginPhase::Done;
+                    self.finish_multi_select();
                 }
             }
+            // With nothing typed, Backspace undoes the last selection
+            BareKey::Backspace if self.multi_mode && self.input.is_empty() => {
+                self.multi_selection.undo();
+            }
             BareKey::Backspace => {
                 self.input.pop();
             }
+            BareKey::Delete if self.multi_mode => {
+                self.multi_selection.clear();
+                self.input.clear();
+            }
             BareKey::Char(c) => {
                 self.input.push(c.to_ascii_lowercase());
-                self.try_match();
+                self.try_match(action_key(&key, c));
             }
             _ => {}
         }
     }
 
-    fn try_match(&mut self) {
+    /// Run the main action on every selected match and close.
+    fn finish_multi_select(&mut self) {
+        let mut picks = self.multi_selection.picks().to_vec();
+        if self.config.multi_sort_by_position {
+            picks.sort_by_key(|pick| pick.position);
+        }
+        let items: Vec<Item> = picks
+            .into_iter()
+            .map(|pick| {
+                let cwd = pick.pane_id.and_then(|id| self.pane_cwds.get(&id));
+                let text = action::absolutize_item(
+                    &self.config,
+                    pick.text(),
+                    &pick.pattern,
+                    cwd.map(PathBuf::as_path),
+                );
+                Item {
+                    text,
+                    pane_id: pick.pane_id,
+                }
+            })
+            .collect();
+        if !items.is_empty() {
+            // Commands run in the matches' pane only if they share one
+            let pane_id = items
+                .first()
+                .and_then(|item| item.pane_id)
+                .filter(|id| items.iter().all(|item| item.pane_id == Some(*id)));
+            let texts: Vec<String> = items.iter().map(|item| item.text.clone()).collect();
+            let selection = Selection {
+                text: action::format_multi(&self.config, &texts),
+                pattern: None,
+                pane_id,
+                position: None,
+                jump_offset: None,
+                pane_rows: None,
+                cwd: pane_id.and_then(|id| self.pane_cwds.get(&id).cloned()),
+                groups: BTreeMap::new(),
+                items,
+            };
+            self.run_action(ActionKey::Main, selection);
+        } else {
+            self.close();
+        }
+    }
+
+    fn scroll_up(&mut self, amount: usize) {
+        if self.config.all_panes {
+            return;
+        }
+        let max = self.hinter.as_ref().map_or(0, |h| {
+            renderer::max_scroll_offset(h, self.viewport_rows, self.viewport_cols)
+        });
+        self.scroll_offset = (self.scroll_offset + amount.max(1)).min(max);
+        // Hints are reassigned for the new viewport
+        self.input.clear();
+    }
+
+    fn scroll_down(&mut self, amount: usize) {
+        if self.config.all_panes {
+            return;
+        }
+        self.scroll_offset = self.scroll_offset.saturating_sub(amount.max(1));
+        self.input.clear();
+    }
+
+    fn try_match(&mut self, action_key: ActionKey) {
         if let Some(ref hinter) = self.hinter {
             if let Some(target) = hinter.lookup(&self.input) {
-                let text = target.text.clone();
+                let origin = self.pane_for_line(target.line);
                 if self.multi_mode {
-                    self.multi_matches.push(text);
-                    self.selected_hints.push(self.input.clone());
+                    // Picking a selected match again deselects it
+                    let pick = Pick {
+                        key: (hinter.pane_of(target.line), target.text.clone()),
+                        position: self.screen_position(hinter, target.line, target.columns.start),
+                        pattern: target.pattern.clone(),
+                        pane_id: origin.map(|(id, _)| id),
+                    };
+                    self.multi_selection.toggle(pick);
                     self.input.clear();
                 } else {
-                    action::execute_action(&self.config, &text);
-                    close_self();
-                    self.phase = PluginPhase::Done;
+                    let selection = Selection {
+                        text: target.text.clone(),
+                        pattern: Some(target.pattern.clone()),
+                        pane_id: origin.map(|(id, _)| id),
+                        position: origin.map(|(_, line)| (line, target.columns.start)),
+                        jump_offset: self.jump_offset(hinter, target.line, target.columns.start),
+                        pane_rows: origin
+                            .and_then(|(id, _)| self.panes.iter().find(|pane| pane.id == id))
+                            .map(|pane| pane.geometry.rows),
+                        cwd: origin.and_then(|(id, _)| self.pane_cwds.get(&id).cloned()),
+                        groups: target.groups.clone(),
+                        items: vec![Item {
+                            text: target.text.clone(),
+                            pane_id: origin.map(|(id, _)| id),
+                        }],
+                    };
+                    self.run_action(action_key, selection);
                 }
             }
         }
     }
 }
+
+/// Rows a scrolling key moves the overlay up, or down when negative, for a
+/// view `page` rows high. `Ctrl u`/`Ctrl d` always scroll, even though most
+/// alphabets contain `u` and `d`, so `ctrl_action` can't pick a hint ending
+/// in them.
+fn scroll_rows(key: &KeyWithModifier, page: usize) -> Option<isize> {
+    let page = page as isize;
+    match key.bare_key {
+        BareKey::PageUp => Some(page),
+        BareKey::PageDown => Some(-page),
+        BareKey::Up => Some(page / 2),
+        BareKey::Down => Some(-page / 2),
+        BareKey::Char('u') if key.has_modifiers(&[KeyModifier::Ctrl]) => Some(page / 2),
+        BareKey::Char('d') if key.has_modifiers(&[KeyModifier::Ctrl]) => Some(-page / 2),
+        _ => None,
+    }
+}
+
+/// The action picked by the modifier held on the final hint key. Shift may
+/// only show up as an uppercase character.
+fn action_key(key: &KeyWithModifier, c: char) -> ActionKey {
+    if key.has_modifiers(&[KeyModifier::Ctrl]) {
+        ActionKey::Ctrl
+    } else if key.has_modifiers(&[KeyModifier::Alt]) {
+        ActionKey::Alt
+    } else if key.has_modifiers(&[KeyModifier::Shift]) || c.is_ascii_uppercase() {
+        ActionKey::Shift
+    } else {
+        ActionKey::Main
+    }
+}
+
+/// Move and resize the plugin's floating pane.
+fn move_overlay(x: &str, y: &str, width: &str, height: &str) {
+    let pane_id = PaneId::Plugin(get_plugin_ids().plugin_id);
+    if let Some(coords) = FloatingPaneCoordinates::new(
+        Some(x.to_string()),
+        Some(y.to_string()),
+        Some(width.to_string()),
+        Some(height.to_string()),
+        None,
+    ) {
+        change_floating_panes_coordinates(vec![(pane_id, coords)]);
+    }
+}
+
+/// Whether a timer armed for `deadline` is due. Timers from earlier phases
+/// or superseded refreshes fire before their replacement's deadline.
+fn deadline_passed(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ctrl_u_and_d_scroll_with_the_default_alphabet() {
+        let config = Config::default();
+        assert!(config.alphabet.iter().any(|c| c == "u"));
+        assert!(config.alphabet.iter().any(|c| c == "d"));
+
+        let ctrl = |c| KeyWithModifier::new(BareKey::Char(c)).with_ctrl_modifier();
+        assert_eq!(scroll_rows(&ctrl('u'), 40), Some(20));
+        assert_eq!(scroll_rows(&ctrl('d'), 40), Some(-20));
+        assert_eq!(
+            scroll_rows(&KeyWithModifier::new(BareKey::Char('u')), 40),
+            None
+        );
+        assert_eq!(action_key(&ctrl('f'), 'f'), ActionKey::Ctrl);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::hinter::MatchKey;
+
+/// A match picked in multi-select mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pick {
+    /// Pane index and text. Hints are reassigned when the view scrolls or
+    /// refreshes, so a pick is remembered by what was matched instead.
+    pub key: MatchKey,
+    /// Screen row and column, for ordering by screen position.
+    pub position: (usize, usize),
+    pub pattern: String,
+    /// The pane the match was captured from.
+    pub pane_id: Option<u32>,
+}
+
+impl Pick {
+    pub fn text(&self) -> &str {
+        &self.key.1
+    }
+}
+
+/// The matches picked in multi-select mode, in the order they were picked.
+#[derive(Default)]
+pub struct MultiSelection {
+    picks: Vec<Pick>,
+}
+
+impl MultiSelection {
+    /// Pick a match, or drop it if it was picked already.
+    pub fn toggle(&mut self, pick: Pick) {
+        match self.picks.iter().position(|p| p.key == pick.key) {
+            Some(index) => {
+                self.picks.remove(index);
+            }
+            None => self.picks.push(pick),
+        }
+    }
+
+    /// Drop the most recent pick.
+    pub fn undo(&mut self) {
+        self.picks.pop();
+    }
+
+    pub fn clear(&mut self) {
+        self.picks.clear();
+    }
+
+    pub fn picks(&self) -> &[Pick] {
+        &self.picks
+    }
+
+    /// The picked matches, for styling them when rendering.
+    pub fn keys(&self) -> Vec<MatchKey> {
+        self.picks.iter().map(|pick| pick.key.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pick(pane: usize, text: &str) -> Pick {
+        Pick {
+            key: (pane, text.to_string()),
+            position: (0, 0),
+            pattern: "path".to_string(),
+            pane_id: Some(pane as u32),
+        }
+    }
+
+    fn texts(selection: &MultiSelection) -> Vec<&str> {
+        selection.picks().iter().map(Pick::text).collect()
+    }
+
+    #[test]
+    fn toggling_a_picked_match_drops_it() {
+        let mut selection = MultiSelection::default();
+        selection.toggle(pick(0, "a"));
+        selection.toggle(pick(0, "b"));
+        selection.toggle(pick(0, "a"));
+        assert_eq!(texts(&selection), ["b"]);
+    }
+
+    #[test]
+    fn equal_texts_in_different_panes_are_different_picks() {
+        let mut selection = MultiSelection::default();
+        selection.toggle(pick(0, "a"));
+        selection.toggle(pick(1, "a"));
+        assert_eq!(
+            selection.keys(),
+            [(0, "a".to_string()), (1, "a".to_string())]
+        );
+    }
+
+    #[test]
+    fn undo_drops_the_latest_pick_and_clear_drops_all() {
+        let mut selection = MultiSelection::default();
+        selection.toggle(pick(0, "a"));
+        selection.toggle(pick(0, "b"));
+        selection.undo();
+        assert_eq!(texts(&selection), ["a"]);
+
+        selection.toggle(pick(0, "c"));
+        selection.clear();
+        assert!(selection.picks().is_empty());
+        selection.undo();
+        assert!(selection.picks().is_empty());
+    }
+}
@@ -1,45 +1,692 @@
 use std::collections::BTreeMap;
 
+use regex::Regex;
 use zellij_tile::prelude::*;
 
-const CAPTURE_FILE: &str = "/tmp/zellij-fingers-capture";
+/// Directory holding capture files, private to the current user.
+const CAPTURE_DIR: &str = r#"${TMPDIR:-/tmp}/zellij-fingers-$(id -u)"#;
+
+/// `RunCommandResult` context key used to tell the plugin's commands apart.
+pub const CONTEXT_KIND: &str = "kind";
+pub const CONTEXT_PANE_ID: &str = "pane_id";
+pub const KIND_CAPTURE: &str = "capture";
+pub const KIND_CLEANUP: &str = "cleanup";
+pub const KIND_CWD: &str = "cwd";
+
+/// Dump the target pane's content and read it back. With `full` the whole
+/// scrollback is included, otherwise only the visible screen.
+///
+/// `zellij action dump-screen` always dumps the focused pane, so the target
+/// pane is focused first. The plugin pane is re-focused by the caller once the
+/// `RunCommandResult` arrives.
+///
+/// The capture file is keyed by session and pane so concurrent sessions never
+/// share it, and it is created with a `077` umask since it holds raw terminal
+/// output. Call [`cleanup_pane_capture`] once the result has been consumed.
+pub fn request_pane_capture(session_name: &str, target_pane_id: u32, full: bool) {
+    focus_terminal_pane(target_pane_id, false);
+
+    let script = format!(
+        concat!(
+            "umask 077; dir=\"{dir}\"; ",
+            "mkdir -p \"$dir\" && chmod 700 \"$dir\" || exit 1; ",
+            "file=\"$dir/$2\"; ",
+            ": > \"$file\" && ",
+            "zellij --session \"$1\" action dump-screen $3 \"$file\" && ",
+            "cat \"$file\""
+        ),
+        dir = CAPTURE_DIR
+    );
+    let file_name = capture_file_name(session_name, target_pane_id);
+    let scrollback_flag = if full { "--full" } else { "" };
+
+    let mut context = context_for(KIND_CAPTURE);
+    context.insert(CONTEXT_PANE_ID.to_string(), target_pane_id.to_string());
 
-/// Read pane content that was pre-dumped by the DumpScreen keybinding action.
-/// The keybinding calls `DumpScreen` before launching the plugin, so the file
-/// already exists when the plugin starts.
-pub fn request_pane_capture() {
-    let context = BTreeMap::new();
     run_command(
-        &["cat", CAPTURE_FILE],
+        &[
+            "sh",
+            "-c",
+            &script,
+            "sh",
+            session_name,
+            &file_name,
+            scrollback_flag,
+        ],
         context,
     );
 }
 
-/// Find the target pane from a PaneManifest: the focused terminal pane
-/// in the current tab that is not a plugin pane.
-pub fn find_target_pane(manifest: &PaneManifest) -> Option<u32> {
-    for panes in manifest.panes.values() {
-        for pane in panes {
-            if pane.is_focused && !pane.is_plugin {
-                return Some(pane.id);
+/// Delete the capture file written by [`request_pane_capture`].
+pub fn cleanup_pane_capture(session_name: &str, target_pane_id: u32) {
+    let script = format!("rm -f \"{}/$1\"", CAPTURE_DIR);
+    let file_name = capture_file_name(session_name, target_pane_id);
+
+    run_command(
+        &["sh", "-c", &script, "sh", &file_name],
+        context_for(KIND_CLEANUP),
+    );
+}
+
+/// Look up the working directory of a terminal pane's shell.
+///
+/// Zellij has no plugin API for this, so the processes of the pane are found
+/// through `/proc` by the `ZELLIJ_PANE_ID` and `ZELLIJ_SESSION_NAME` Zellij
+/// sets in their environment. The one whose parent is not part of the pane is
+/// the pane's shell. Only works where `/proc` is available (Linux); elsewhere
+/// the command prints nothing.
+pub fn request_pane_cwd(session_name: &str, pane_id: u32) {
+    let script = concat!(
+        "in_pane() { [ \"$({ tr '\\0' '\\n' < \"/proc/$1/environ\"; } 2>/dev/null | ",
+        "grep -cxF -e \"ZELLIJ_PANE_ID=$2\" -e \"ZELLIJ_SESSION_NAME=$3\")\" = 2 ]; }; ",
+        "for dir in /proc/[0-9]*; do ",
+        "pid=${dir#/proc/}; in_pane \"$pid\" \"$1\" \"$2\" || continue; ",
+        "ppid=$(sed 's/.*) . \\([0-9]*\\).*/\\1/' \"$dir/stat\" 2>/dev/null); ",
+        "in_pane \"$ppid\" \"$1\" \"$2\" && continue; ",
+        "readlink \"$dir/cwd\" && exit 0; ",
+        "done"
+    );
+
+    let mut context = context_for(KIND_CWD);
+    context.insert(CONTEXT_PANE_ID.to_string(), pane_id.to_string());
+
+    run_command(
+        &["sh", "-c", script, "sh", &pane_id.to_string(), session_name],
+        context,
+    );
+}
+
+/// Turn a failed capture command into a message for the error screen.
+pub fn describe_capture_error(exit_code: Option<i32>, stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let stderr = stderr.trim();
+
+    let reason = if stderr.contains("Permission denied") {
+        "permission denied"
+    } else if stderr.contains("No such file") {
+        "capture file is missing"
+    } else if stderr.contains("zellij: not found") || stderr.contains("command not found") {
+        "`zellij` was not found on PATH"
+    } else {
+        "capture command failed"
+    };
+
+    let exit = exit_code.map_or_else(|| "killed".to_string(), |code| format!("exit code {code}"));
+    if stderr.is_empty() {
+        format!("Could not capture pane: {reason} ({exit})")
+    } else {
+        format!("Could not capture pane: {reason} ({exit})\n{stderr}")
+    }
+}
+
+fn context_for(kind: &str) -> BTreeMap<String, String> {
+    BTreeMap::from([(CONTEXT_KIND.to_string(), kind.to_string())])
+}
+
+/// Capture file name for a pane, with the session name reduced to
+/// characters that are safe in a path component.
+fn capture_file_name(session_name: &str, pane_id: u32) -> String {
+    let session: String = session_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
             }
+        })
+        .collect();
+    format!("{session}-{pane_id}")
+}
+
+/// Which pane to hint, parsed from the `target_pane` option.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TargetPane {
+    /// The focused terminal pane.
+    Focused,
+    /// The tiled pane next to the focused pane in the given direction.
+    Adjacent(Direction),
+    /// The focused pane of the other layer: the focused tiled pane when a
+    /// floating pane has focus, and the other way around. Also accepted as
+    /// `last-focused`: Zellij keeps no focus history like tmux's last pane,
+    /// but the other layer's pane is the one focus left when toggling
+    /// floating panes.
+    OtherLayer,
+    /// A terminal pane by id.
+    Id(u32),
+    /// The first terminal pane whose title matches the regex.
+    Title(String),
+}
+
+impl TargetPane {
+    /// Parse a `target_pane` value. A title regex that doesn't compile is an
+    /// error rather than silently meaning the focused pane.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        Ok(match value.trim() {
+            "" | "focused" => Self::Focused,
+            "left" => Self::Adjacent(Direction::Left),
+            "right" => Self::Adjacent(Direction::Right),
+            "up" => Self::Adjacent(Direction::Up),
+            "down" => Self::Adjacent(Direction::Down),
+            "other-layer" | "last-focused" => Self::OtherLayer,
+            other => match other.parse::<u32>() {
+                Ok(id) => Self::Id(id),
+                Err(_) => {
+                    if let Err(err) = Regex::new(other) {
+                        return Err(format!(
+                            "Invalid target_pane title pattern `{other}`: {err}"
+                        ));
+                    }
+                    Self::Title(other.to_string())
+                }
+            },
+        })
+    }
+}
+
+/// Position of the active tab, which keys its panes in the `PaneManifest`.
+pub fn active_tab_position(tabs: &[TabInfo]) -> Option<usize> {
+    tabs.iter().find(|tab| tab.active).map(|tab| tab.position)
+}
+
+/// Find the target pane among the panes of the active tab. Every tab has
+/// its own focused pane, so panes from other tabs are never considered.
+/// Falls back to the focused pane when the requested pane cannot be found.
+pub fn find_target_pane(
+    manifest: &PaneManifest,
+    active_tab: usize,
+    target: &TargetPane,
+) -> Option<u32> {
+    let panes = manifest.panes.get(&active_tab)?;
+    let focused = find_focused_pane(panes)?;
+    let terminals = || panes.iter().filter(|p| !p.is_plugin && !p.is_suppressed);
+
+    let found = match target {
+        TargetPane::Focused => None,
+        TargetPane::Adjacent(direction) => find_adjacent_pane(panes, focused, *direction),
+        TargetPane::OtherLayer => terminals()
+            .find(|p| p.is_focused && p.is_floating != focused.is_floating)
+            .map(|p| p.id),
+        TargetPane::Id(id) => terminals().find(|p| p.id == *id).map(|p| p.id),
+        // Checked when parsing
+        TargetPane::Title(pattern) => Regex::new(pattern)
+            .ok()
+            .and_then(|re| terminals().find(|p| re.is_match(&p.title)))
+            .map(|p| p.id),
+    };
+
+    found.or(Some(focused.id))
+}
+
+/// The focused terminal pane that is not a plugin pane.
+fn find_focused_pane(panes: &[PaneInfo]) -> Option<&PaneInfo> {
+    panes.iter().find(|pane| pane.is_focused && !pane.is_plugin)
+}
+
+/// The tiled pane that borders `from` on the given side, preferring the
+/// nearest one and then the one sharing the longest edge.
+fn find_adjacent_pane(panes: &[PaneInfo], from: &PaneInfo, direction: Direction) -> Option<u32> {
+    let overlap = |a_start: usize, a_len: usize, b_start: usize, b_len: usize| {
+        (a_start + a_len)
+            .min(b_start + b_len)
+            .saturating_sub(a_start.max(b_start))
+    };
+
+    panes
+        .iter()
+        .filter(|p| !p.is_plugin && !p.is_suppressed && !p.is_floating && p.id != from.id)
+        .filter_map(|p| {
+            let (distance, shared) = match direction {
+                Direction::Left if p.pane_x + p.pane_columns <= from.pane_x => (
+                    from.pane_x - (p.pane_x + p.pane_columns),
+                    overlap(p.pane_y, p.pane_rows, from.pane_y, from.pane_rows),
+                ),
+                Direction::Right if p.pane_x >= from.pane_x + from.pane_columns => (
+                    p.pane_x - (from.pane_x + from.pane_columns),
+                    overlap(p.pane_y, p.pane_rows, from.pane_y, from.pane_rows),
+                ),
+                Direction::Up if p.pane_y + p.pane_rows <= from.pane_y => (
+                    from.pane_y - (p.pane_y + p.pane_rows),
+                    overlap(p.pane_x, p.pane_columns, from.pane_x, from.pane_columns),
+                ),
+                Direction::Down if p.pane_y >= from.pane_y + from.pane_rows => (
+                    p.pane_y - (from.pane_y + from.pane_rows),
+                    overlap(p.pane_x, p.pane_columns, from.pane_x, from.pane_columns),
+                ),
+                _ => return None,
+            };
+            (shared > 0).then_some((distance, std::cmp::Reverse(shared), p.id))
+        })
+        .min()
+        .map(|(_, _, id)| id)
+}
+
+/// Captured content of one pane along with where it sits on screen.
+///
+/// `dump-screen` joins soft-wrapped rows back into the logical line they came
+/// from, so `lines` are logical lines; the renderer splits them into physical
+/// rows again using the pane width.
+pub struct CapturedPane {
+    pub id: u32,
+    pub geometry: PaneGeometry,
+    pub lines: Vec<String>,
+}
+
+/// Position and size of a pane's content area, in screen cells.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PaneGeometry {
+    pub x: usize,
+    pub y: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl PaneGeometry {
+    fn of(pane: &PaneInfo) -> Self {
+        Self {
+            x: pane.pane_content_x,
+            y: pane.pane_content_y,
+            rows: pane.pane_content_rows,
+            cols: pane.pane_content_columns,
         }
     }
-    None
 }
 
-/// Get the dimensions (rows, cols) of a specific pane.
-pub fn get_pane_dimensions(
+/// Get the content geometry of a specific pane.
+pub fn get_pane_geometry(
     manifest: &PaneManifest,
-    target_id: Option<u32>,
-) -> Option<(usize, usize)> {
-    let target_id = target_id?;
-    for panes in manifest.panes.values() {
-        for pane in panes {
-            if pane.id == target_id {
-                return Some((pane.pane_content_rows, pane.pane_columns));
+    pane_id: u32,
+    is_plugin: bool,
+) -> Option<PaneGeometry> {
+    manifest
+        .panes
+        .values()
+        .flatten()
+        .find(|pane| pane.id == pane_id && pane.is_plugin == is_plugin)
+        .map(PaneGeometry::of)
+}
+
+/// Cells taken by a pane's frame on each side of its content area.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameInsets {
+    pub left: usize,
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+}
+
+impl FrameInsets {
+    /// The frame of the plugin's own pane, from where its content area sits
+    /// inside the pane.
+    pub fn of_plugin(manifest: &PaneManifest, plugin_id: u32) -> Option<Self> {
+        let pane = manifest
+            .panes
+            .values()
+            .flatten()
+            .find(|pane| pane.id == plugin_id && pane.is_plugin)?;
+        let left = pane.pane_content_x.saturating_sub(pane.pane_x);
+        let top = pane.pane_content_y.saturating_sub(pane.pane_y);
+        Some(Self {
+            left,
+            top,
+            right: pane
+                .pane_columns
+                .saturating_sub(pane.pane_content_columns + left),
+            bottom: pane.pane_rows.saturating_sub(pane.pane_content_rows + top),
+        })
+    }
+
+    /// The outer geometry of a pane with this frame whose content area
+    /// covers `content` exactly.
+    pub fn around(&self, content: &PaneGeometry) -> PaneGeometry {
+        PaneGeometry {
+            x: content.x.saturating_sub(self.left),
+            y: content.y.saturating_sub(self.top),
+            rows: content.rows + self.top + self.bottom,
+            cols: content.cols + self.left + self.right,
+        }
+    }
+}
+
+/// Top-left corner of the tab's viewport, which floating pane coordinates
+/// are relative to. UI bars such as the tab bar are not selectable and lie
+/// outside of it.
+pub fn viewport_origin(manifest: &PaneManifest, tab: usize) -> (usize, usize) {
+    let tiled = || {
+        manifest
+            .panes
+            .get(&tab)
+            .into_iter()
+            .flatten()
+            .filter(|p| p.is_selectable && !p.is_floating && !p.is_suppressed)
+    };
+    (
+        tiled().map(|p| p.pane_x).min().unwrap_or(0),
+        tiled().map(|p| p.pane_y).min().unwrap_or(0),
+    )
+}
+
+/// All visible terminal panes in the tab containing `target_id`, in the
+/// order they appear in the manifest.
+pub fn find_tab_panes(manifest: &PaneManifest, target_id: u32) -> Vec<(u32, PaneGeometry)> {
+    let tab = manifest
+        .panes
+        .values()
+        .find(|panes| panes.iter().any(|p| p.id == target_id && !p.is_plugin));
+
+    tab.map(|panes| {
+        panes
+            .iter()
+            .filter(|p| !p.is_plugin && !p.is_suppressed)
+            .map(|p| (p.id, PaneGeometry::of(p)))
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn capture_file_name_is_keyed_by_session_and_pane() {
+        assert_eq!(capture_file_name("dev", 3), "dev-3");
+        assert_ne!(capture_file_name("dev", 3), capture_file_name("dev", 4));
+        assert_ne!(capture_file_name("dev", 3), capture_file_name("ops", 3));
+    }
+
+    #[test]
+    fn capture_file_name_sanitizes_session_name() {
+        assert_eq!(capture_file_name("../we ird/", 1), "___we_ird_-1");
+    }
+
+    #[test]
+    fn find_tab_panes_returns_visible_terminals_of_the_target_tab() {
+        let pane = |id, is_plugin, is_suppressed| PaneInfo {
+            id,
+            is_plugin,
+            is_suppressed,
+            pane_content_x: id as usize * 10,
+            pane_content_rows: 5,
+            pane_content_columns: 10,
+            ..Default::default()
+        };
+        let manifest = PaneManifest {
+            panes: HashMap::from([
+                (
+                    0,
+                    vec![
+                        pane(1, false, false),
+                        pane(2, true, false),
+                        pane(3, false, true),
+                    ],
+                ),
+                (1, vec![pane(4, false, false), pane(5, false, false)]),
+            ]),
+        };
+
+        let panes = find_tab_panes(&manifest, 5);
+        let ids: Vec<u32> = panes.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![4, 5]);
+        assert_eq!(
+            panes[1].1,
+            PaneGeometry {
+                x: 50,
+                y: 0,
+                rows: 5,
+                cols: 10
             }
+        );
+
+        let ids: Vec<u32> = find_tab_panes(&manifest, 1)
+            .iter()
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    fn tiled(id: u32, x: usize, y: usize, columns: usize, rows: usize) -> PaneInfo {
+        PaneInfo {
+            id,
+            pane_x: x,
+            pane_y: y,
+            pane_columns: columns,
+            pane_rows: rows,
+            title: format!("pane-{id}"),
+            ..Default::default()
         }
     }
-    None
+
+    /// Four tiled panes in a 2x2 grid with pane 1 (top left) focused.
+    fn grid_manifest() -> PaneManifest {
+        let mut focused = tiled(1, 0, 0, 40, 10);
+        focused.is_focused = true;
+        PaneManifest {
+            panes: HashMap::from([(
+                0,
+                vec![
+                    focused,
+                    tiled(2, 40, 0, 40, 10),
+                    tiled(3, 0, 10, 40, 10),
+                    tiled(4, 40, 10, 40, 10),
+                ],
+            )]),
+        }
+    }
+
+    #[test]
+    fn target_pane_parses_all_forms() {
+        assert_eq!(TargetPane::parse("focused"), Ok(TargetPane::Focused));
+        assert_eq!(
+            TargetPane::parse("up"),
+            Ok(TargetPane::Adjacent(Direction::Up))
+        );
+        assert_eq!(TargetPane::parse("other-layer"), Ok(TargetPane::OtherLayer));
+        assert_eq!(
+            TargetPane::parse("last-focused"),
+            Ok(TargetPane::OtherLayer)
+        );
+        assert_eq!(TargetPane::parse("12"), Ok(TargetPane::Id(12)));
+        assert_eq!(
+            TargetPane::parse("^logs"),
+            Ok(TargetPane::Title("^logs".to_string()))
+        );
+    }
+
+    #[test]
+    fn target_pane_rejects_invalid_title_patterns() {
+        let err = TargetPane::parse("logs(").unwrap_err();
+        assert!(err.starts_with("Invalid target_pane title pattern `logs(`"));
+    }
+
+    #[test]
+    fn finds_adjacent_panes_geometrically() {
+        let manifest = grid_manifest();
+        let find = |value| find_target_pane(&manifest, 0, &TargetPane::parse(value).unwrap());
+
+        assert_eq!(find("focused"), Some(1));
+        assert_eq!(find("right"), Some(2));
+        assert_eq!(find("down"), Some(3));
+        // Nothing to the left of the top left pane: fall back to focused
+        assert_eq!(find("left"), Some(1));
+    }
+
+    #[test]
+    fn finds_panes_by_id_and_title() {
+        let manifest = grid_manifest();
+        let find = |value| find_target_pane(&manifest, 0, &TargetPane::parse(value).unwrap());
+
+        assert_eq!(find("4"), Some(4));
+        assert_eq!(find("pane-3$"), Some(3));
+        assert_eq!(find("99"), Some(1));
+    }
+
+    #[test]
+    fn other_layer_is_the_focused_pane_of_the_other_layer() {
+        let mut manifest = grid_manifest();
+        let mut floating = tiled(5, 10, 5, 20, 5);
+        floating.is_floating = true;
+        floating.is_focused = true;
+        manifest.panes.get_mut(&0).unwrap().push(floating);
+        let find = |manifest: &PaneManifest| {
+            find_target_pane(manifest, 0, &TargetPane::parse("other-layer").unwrap())
+        };
+
+        // Both layers have a focused pane; the tiled one comes first
+        assert_eq!(find(&manifest), Some(5));
+        manifest.panes.get_mut(&0).unwrap().pop();
+        // Without a floating pane there is no other layer
+        assert_eq!(find(&manifest), Some(1));
+    }
+
+    #[test]
+    fn active_tab_position_uses_the_active_tab() {
+        let tabs = vec![
+            TabInfo {
+                position: 0,
+                ..Default::default()
+            },
+            TabInfo {
+                position: 1,
+                active: true,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(active_tab_position(&tabs), Some(1));
+        assert_eq!(active_tab_position(&[]), None);
+    }
+
+    #[test]
+    fn only_considers_panes_of_the_active_tab() {
+        // Each tab has its own focused pane
+        let mut in_first = tiled(1, 0, 0, 80, 20);
+        in_first.is_focused = true;
+        let mut in_second = tiled(2, 0, 0, 80, 20);
+        in_second.is_focused = true;
+        let manifest = PaneManifest {
+            panes: HashMap::from([
+                (0, vec![in_first]),
+                (1, vec![in_second, tiled(3, 0, 20, 80, 5)]),
+            ]),
+        };
+        let tabs = vec![
+            TabInfo {
+                position: 0,
+                ..Default::default()
+            },
+            TabInfo {
+                position: 1,
+                active: true,
+                ..Default::default()
+            },
+        ];
+        let active = active_tab_position(&tabs).unwrap();
+
+        assert_eq!(
+            find_target_pane(&manifest, active, &TargetPane::Focused),
+            Some(2)
+        );
+        assert_eq!(
+            find_target_pane(&manifest, active, &TargetPane::Id(3)),
+            Some(3)
+        );
+        // Pane 1 lives in another tab
+        assert_eq!(
+            find_target_pane(&manifest, active, &TargetPane::Id(1)),
+            Some(2)
+        );
+        assert_eq!(find_target_pane(&manifest, 2, &TargetPane::Focused), None);
+    }
+
+    #[test]
+    fn describes_capture_errors() {
+        let missing = describe_capture_error(Some(1), b"cat: /tmp/x: No such file or directory\n");
+        assert_eq!(
+            missing,
+            "Could not capture pane: capture file is missing (exit code 1)\ncat: /tmp/x: No such file or directory"
+        );
+
+        let denied = describe_capture_error(Some(1), b"sh: /tmp/x: Permission denied");
+        assert!(denied.starts_with("Could not capture pane: permission denied"));
+
+        let unknown = describe_capture_error(None, b"");
+        assert_eq!(
+            unknown,
+            "Could not capture pane: capture command failed (killed)"
+        );
+    }
+
+    #[test]
+    fn frame_insets_cover_the_target_content_area() {
+        let plugin = PaneInfo {
+            id: 7,
+            is_plugin: true,
+            pane_x: 10,
+            pane_y: 5,
+            pane_columns: 40,
+            pane_rows: 20,
+            pane_content_x: 11,
+            pane_content_y: 6,
+            pane_content_columns: 38,
+            pane_content_rows: 18,
+            ..Default::default()
+        };
+        let manifest = PaneManifest {
+            panes: HashMap::from([(0, vec![plugin])]),
+        };
+
+        let insets = FrameInsets::of_plugin(&manifest, 7).unwrap();
+        assert_eq!(
+            insets,
+            FrameInsets {
+                left: 1,
+                top: 1,
+                right: 1,
+                bottom: 1
+            }
+        );
+        assert!(FrameInsets::of_plugin(&manifest, 8).is_none());
+
+        let target = PaneGeometry {
+            x: 41,
+            y: 2,
+            rows: 10,
+            cols: 39,
+        };
+        assert_eq!(
+            insets.around(&target),
+            PaneGeometry {
+                x: 40,
+                y: 1,
+                rows: 12,
+                cols: 41
+            }
+        );
+    }
+
+    #[test]
+    fn viewport_origin_skips_ui_bars_and_floating_panes() {
+        let pane = |id, y, is_selectable, is_floating| PaneInfo {
+            id,
+            pane_x: id as usize,
+            pane_y: y,
+            is_selectable,
+            is_floating,
+            ..Default::default()
+        };
+        let manifest = PaneManifest {
+            panes: HashMap::from([(
+                0,
+                vec![
+                    pane(0, 0, false, false),
+                    pane(2, 1, true, false),
+                    pane(1, 3, true, true),
+                ],
+            )]),
+        };
+
+        assert_eq!(viewport_origin(&manifest, 0), (2, 1));
+        assert_eq!(viewport_origin(&manifest, 1), (0, 0));
+    }
 }
@@ -1,30 +1,126 @@
-use crate::hinter::Hinter;
+use std::ops::Range;
+
+use crate::ansi;
+use crate::hinter::{Hinter, MatchKey};
+use crate::pane_capture::CapturedPane;
 
 const HIDE_CURSOR: &str = "\x1b[?25l";
 
 /// Renders the hinter output as ANSI text for the plugin's render() callback.
 /// Zellij auto-clears between render calls, so we just output the content.
+///
+/// Long lines are soft-wrapped at `cols` like the terminal did, and
+/// `scroll_offset` is the number of rows the view is scrolled up from the
+/// bottom of the captured scrollback.
 pub fn render(
     hinter: &mut Hinter,
     input_prefix: &str,
-    selected_hints: &[String],
+    selected: &[MatchKey],
     rows: usize,
     cols: usize,
+    scroll_offset: usize,
+) -> String {
+    let lines = 0..hinter.line_count();
+    let total = total_rows(hinter, lines.clone(), cols);
+    let end = total.saturating_sub(scroll_offset);
+    let start = end.saturating_sub(rows);
+    let (visible, skip) = lines_for_rows(hinter, lines, cols, start..end);
+
+    let formatted = hinter.run(input_prefix, selected, cols, visible);
+    let rows: Vec<String> = formatted
+        .iter()
+        .flat_map(|line| ansi::split_rows(&line.content, cols))
+        .skip(skip)
+        .take(end - start)
+        .collect();
+
+    let mut output = String::new();
+    output.push_str(HIDE_CURSOR);
+    output.push_str(&rows.join("\n"));
+    output
+}
+
+/// Renders every captured pane at its own position, sharing one hint space.
+///
+/// `origin` is the screen position of the overlay's top-left content cell;
+/// panes are drawn relative to it with absolute cursor moves.
+pub fn render_panes(
+    hinter: &mut Hinter,
+    input_prefix: &str,
+    selected: &[MatchKey],
+    panes: &[CapturedPane],
+    origin: (usize, usize),
 ) -> String {
-    let lines = hinter.run(input_prefix, selected_hints, cols);
+    let mut regions = Vec::new();
+    let mut skips = Vec::new();
+    let mut first_line = 0;
+    for pane in panes {
+        let lines = first_line..first_line + pane.lines.len();
+        let cols = pane.geometry.cols;
+        let total = total_rows(hinter, lines.clone(), cols);
+        let rows = total.saturating_sub(pane.geometry.rows)..total;
+        let (visible, skip) = lines_for_rows(hinter, lines.clone(), cols, rows);
+        regions.push((visible, cols));
+        skips.push(skip);
+        first_line = lines.end;
+    }
+
+    let formatted = hinter.run_regions(input_prefix, selected, &regions);
     let mut output = String::new();
 
     output.push_str(HIDE_CURSOR);
 
-    for (i, line) in lines.iter().enumerate() {
-        if i >= rows {
-            break;
-        }
-        output.push_str(&line.content);
-        if i < lines.len() - 1 && i < rows - 1 {
-            output.push('\n');
+    for ((pane, lines), skip) in panes.iter().zip(formatted).zip(skips) {
+        let x = pane.geometry.x.saturating_sub(origin.0);
+        let y = pane.geometry.y.saturating_sub(origin.1);
+        let rows = lines
+            .iter()
+            .flat_map(|line| ansi::split_rows(&line.content, pane.geometry.cols))
+            .skip(skip)
+            .take(pane.geometry.rows);
+        for (row, content) in rows.enumerate() {
+            output.push_str(&format!("\x1b[{};{}H", y + row + 1, x + 1));
+            output.push_str(&content);
         }
     }
 
     output
 }
+
+/// Largest useful scroll offset: the top of the scrollback is in view.
+pub fn max_scroll_offset(hinter: &Hinter, rows: usize, cols: usize) -> usize {
+    total_rows(hinter, 0..hinter.line_count(), cols).saturating_sub(rows)
+}
+
+fn total_rows(hinter: &Hinter, lines: Range<usize>, cols: usize) -> usize {
+    lines.map(|line| hinter.row_count(line, cols)).sum()
+}
+
+/// The lines covering physical `rows`, counted from the start of `lines`,
+/// and how many rows of the first of them fall before `rows.start`.
+fn lines_for_rows(
+    hinter: &Hinter,
+    lines: Range<usize>,
+    cols: usize,
+    rows: Range<usize>,
+) -> (Range<usize>, usize) {
+    let mut start = lines.end;
+    let mut end = lines.end;
+    let mut skip = 0;
+    let mut row = 0;
+
+    for line in lines {
+        let next_row = row + hinter.row_count(line, cols);
+        if start == end && next_row > rows.start {
+            start = line;
+            skip = rows.start - row;
+        }
+        if next_row >= rows.end {
+            end = line + 1;
+            break;
+        }
+        row = next_row;
+    }
+
+    (start.min(end)..end, skip)
+}
@@ -3,5 +3,11 @@ pub enum PluginPhase {
     WaitingForPermissions,
     Capturing,
     Hinting,
+    /// A selection was made; waiting briefly for the action's result.
+    RunningAction,
+    /// A short confirmation shown before closing.
+    Notifying(String),
+    /// Something went wrong; the message is shown until the user presses Esc.
+    Error(String),
     Done,
 }